@@ -0,0 +1,116 @@
+//! Multi-threaded multi-colour Game of Life (Immigration, QuadLife)
+//! - input: finite grid as Vec<u8> (row-major, 0=dead, 1..=N=colour)
+//! - width: number of columns
+//! - variant: number of colours and how births pick theirs
+//! - iterations: number of generations
+//! - report: called after every generation with the population per colour
+//!
+//! returns: Vec<u8> in the same flat format
+//!
+//! Counts follow B3/S23; survivors keep their colour and a newborn takes
+//! the majority colour of its three parents.
+
+use rayon::prelude::*;
+
+#[derive(Clone, Copy)]
+pub enum ColourVariant
+{
+    /// Two colours; three parents always have a majority.
+    Immigration,
+    /// Four colours; three distinct parents give the missing fourth.
+    QuadLife,
+}
+
+impl ColourVariant
+{
+    pub fn colours(self) -> usize
+    {
+        match self {
+            ColourVariant::Immigration => 2,
+            ColourVariant::QuadLife => 4,
+        }
+    }
+
+    fn birth_colour(self, counts: &[u8; 5]) -> u8
+    {
+        let colours = self.colours();
+        if let Some(c) = (1..=colours).find(|&c| counts[c] >= 2) {
+            return c as u8;
+        }
+        (1..=colours).find(|&c| counts[c] == 0).unwrap_or(1) as u8
+    }
+}
+
+/// Number of live cells of each colour; index 0 holds colour 1.
+pub fn colour_population(grid: &[u8], colours: usize) -> Vec<usize>
+{
+    let mut population = vec![0usize; colours];
+    for &cell in grid {
+        if cell != 0 && (cell as usize) <= colours {
+            population[cell as usize - 1] += 1;
+        }
+    }
+    population
+}
+
+pub fn gol_colour<F>(
+    mut curr: Vec<u8>,
+    width: usize,
+    variant: ColourVariant,
+    iterations: usize,
+    mut report: F,
+) -> Vec<u8>
+where
+    F: FnMut(usize, &[usize]),
+{
+    let area = curr.len();
+    let height = area / width;
+
+    let padded_width = width + 2;
+    let padded_height = height + 2;
+
+    let mut padded = vec![0u8; padded_width * padded_height];
+    let mut next = vec![0u8; area];
+
+    let pw = padded_width as isize;
+    let offsets: [isize; 8] =
+        [-(pw + 1), -pw, -(pw - 1), -1, 1, pw - 1, pw, pw + 1];
+
+    for generation in 1..=iterations {
+        for y in 0..height {
+            let src_off = y * width;
+            let dst_off = (y + 1) * padded_width + 1;
+            padded[dst_off..dst_off + width]
+                .copy_from_slice(&curr[src_off..src_off + width]);
+        }
+
+        let padded = &padded;
+        next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            let row_off = ((y + 1) * padded_width + 1) as isize;
+            for (x, out) in row.iter_mut().enumerate() {
+                let idx = row_off + x as isize;
+                let mut counts = [0u8; 5];
+                let mut cnt = 0u8;
+                for &off in &offsets {
+                    let n = padded[(idx + off) as usize];
+                    if n != 0 {
+                        cnt += 1;
+                        counts[(n as usize).min(4)] += 1;
+                    }
+                }
+                let cell = padded[idx as usize];
+                *out = match (cell, cnt) {
+                    (0, 3) => variant.birth_colour(&counts),
+                    (0, _) => 0,
+                    (_, 2) | (_, 3) => cell,
+                    _ => 0,
+                };
+            }
+        });
+        std::mem::swap(&mut curr, &mut next);
+
+        report(generation, &colour_population(&curr, variant.colours()));
+    }
+
+    curr
+}
@@ -0,0 +1,283 @@
+//! Multi-threaded continuous-state automata (SmoothLife, Lenia) using Rayon
+//! - input: finite grid as Vec<f32> (row-major, 0.0=empty, 1.0=full)
+//! - width: number of columns
+//! - rule: kernels, growth function and time step
+//! - iterations: number of time steps
+//!
+//! returns: Vec<f32> in the same flat format
+//!
+//! Neighbourhoods are evaluated by direct convolution: each kernel is
+//! compiled into a list of (offset, weight) taps over a zero-padded buffer,
+//! the same way gol_three walks its fixed 8-neighbour offsets.
+
+use rayon::prelude::*;
+
+pub enum KernelShape
+{
+    /// Uniform disk, used as the SmoothLife inner filling.
+    Disk
+    {
+        radius: f32
+    },
+    /// Uniform ring between two radii, used as the SmoothLife neighbourhood.
+    Annulus
+    {
+        inner: f32, outer: f32
+    },
+    /// Lenia concentric shells, one bump per entry of `peaks`.
+    Shells
+    {
+        radius: f32, peaks: Vec<f32>
+    },
+}
+
+pub struct Kernel
+{
+    radius: usize,
+    taps: Vec<(isize, isize, f32)>,
+}
+
+impl Kernel
+{
+    pub fn new(shape: &KernelShape) -> Kernel
+    {
+        let outer = match shape {
+            KernelShape::Disk { radius } => *radius,
+            KernelShape::Annulus { outer, .. } => *outer,
+            KernelShape::Shells { radius, .. } => *radius,
+        };
+        let radius = outer.ceil() as usize + 1;
+        let r = radius as isize;
+
+        let mut taps = Vec::new();
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let dist = ((dy * dy + dx * dx) as f32).sqrt();
+                let weight = shape.weight(dist);
+                if weight > 0.0 {
+                    taps.push((dy, dx, weight));
+                }
+            }
+        }
+
+        let total: f32 = taps.iter().map(|&(_, _, w)| w).sum();
+        if total > 0.0 {
+            for tap in &mut taps {
+                tap.2 /= total;
+            }
+        }
+
+        Kernel { radius, taps }
+    }
+
+    pub fn radius(&self) -> usize
+    {
+        self.radius
+    }
+
+    pub fn taps(&self) -> &[(isize, isize, f32)]
+    {
+        &self.taps
+    }
+}
+
+impl KernelShape
+{
+    fn weight(&self, dist: f32) -> f32
+    {
+        // Linear anti-aliasing over one cell keeps the discs smooth.
+        let inside = |r: f32| (r + 0.5 - dist).clamp(0.0, 1.0);
+        match self {
+            KernelShape::Disk { radius } => inside(*radius),
+            KernelShape::Annulus { inner, outer } => {
+                inside(*outer) * (1.0 - inside(*inner))
+            }
+            KernelShape::Shells { radius, peaks } => {
+                let r = dist / radius;
+                if peaks.is_empty() || r <= 0.0 || r >= 1.0 {
+                    return 0.0;
+                }
+                let scaled = r * peaks.len() as f32;
+                let shell = (scaled as usize).min(peaks.len() - 1);
+                let local = scaled - shell as f32;
+                peaks[shell] * bump(local)
+            }
+        }
+    }
+}
+
+fn bump(r: f32) -> f32
+{
+    if r <= 0.0 || r >= 1.0 {
+        0.0
+    } else {
+        (4.0 - 1.0 / (r * (1.0 - r))).exp()
+    }
+}
+
+pub enum Growth
+{
+    /// Lenia exponential growth: 2·exp(-(u-μ)²/2σ²) - 1.
+    Gaussian
+    {
+        mu: f32, sigma: f32
+    },
+    /// Lenia polynomial growth: 2·max(0, 1-(u-μ)²/9σ²)⁴ - 1.
+    Polynomial
+    {
+        mu: f32, sigma: f32
+    },
+    /// SmoothLife transition function with sigmoid step widths.
+    SmoothLife
+    {
+        birth: (f32, f32),
+        death: (f32, f32),
+        alpha_n: f32,
+        alpha_m: f32,
+    },
+}
+
+impl Growth
+{
+    fn apply(&self, cell: f32, n: f32, m: f32, dt: f32) -> f32
+    {
+        match *self {
+            Growth::Gaussian { mu, sigma } => {
+                let d = (n - mu) / sigma;
+                let g = 2.0 * (-0.5 * d * d).exp() - 1.0;
+                (cell + dt * g).clamp(0.0, 1.0)
+            }
+            Growth::Polynomial { mu, sigma } => {
+                let d = (n - mu) * (n - mu) / (9.0 * sigma * sigma);
+                let g = 2.0 * (1.0 - d).max(0.0).powi(4) - 1.0;
+                (cell + dt * g).clamp(0.0, 1.0)
+            }
+            Growth::SmoothLife {
+                birth,
+                death,
+                alpha_n,
+                alpha_m,
+            } => {
+                let alive = sigmoid(m, 0.5, alpha_m);
+                let lo = birth.0 * (1.0 - alive) + death.0 * alive;
+                let hi = birth.1 * (1.0 - alive) + death.1 * alive;
+                let target =
+                    sigmoid(n, lo, alpha_n) * (1.0 - sigmoid(n, hi, alpha_n));
+                (cell + dt * (target - cell)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+fn sigmoid(x: f32, a: f32, alpha: f32) -> f32
+{
+    1.0 / (1.0 + (-(x - a) * 4.0 / alpha).exp())
+}
+
+pub struct ContinuousRule
+{
+    /// Neighbourhood kernel whose weighted sum feeds the growth function.
+    pub kernel: Kernel,
+    /// Optional inner filling kernel (SmoothLife `m`); the cell itself
+    /// is used when absent.
+    pub inner: Option<Kernel>,
+    pub growth: Growth,
+    pub dt: f32,
+}
+
+impl ContinuousRule
+{
+    /// Rafler's SmoothLife with outer radius 21 and discrete time steps.
+    pub fn smoothlife() -> ContinuousRule
+    {
+        let outer = 21.0;
+        let inner = outer / 3.0;
+        ContinuousRule {
+            kernel: Kernel::new(&KernelShape::Annulus { inner, outer }),
+            inner: Some(Kernel::new(&KernelShape::Disk { radius: inner })),
+            growth: Growth::SmoothLife {
+                birth: (0.278, 0.365),
+                death: (0.267, 0.445),
+                alpha_n: 0.028,
+                alpha_m: 0.147,
+            },
+            dt: 1.0,
+        }
+    }
+
+    /// Lenia parameters of the Orbium glider (R=13, T=10).
+    pub fn lenia() -> ContinuousRule
+    {
+        ContinuousRule {
+            kernel: Kernel::new(&KernelShape::Shells {
+                radius: 13.0,
+                peaks: vec![1.0],
+            }),
+            inner: None,
+            growth: Growth::Gaussian {
+                mu: 0.15,
+                sigma: 0.015,
+            },
+            dt: 0.1,
+        }
+    }
+}
+
+fn convolve(padded: &[f32], pw: usize, idx: usize, kernel: &Kernel) -> f32
+{
+    let mut sum = 0.0;
+    for &(dy, dx, w) in kernel.taps() {
+        let off = dy * pw as isize + dx;
+        sum += padded[(idx as isize + off) as usize] * w;
+    }
+    sum
+}
+
+pub fn gol_continuous(
+    mut curr: Vec<f32>,
+    width: usize,
+    rule: &ContinuousRule,
+    iterations: usize,
+) -> Vec<f32>
+{
+    let area = curr.len();
+    let height = area / width;
+
+    let margin = rule
+        .inner
+        .as_ref()
+        .map_or(0, |k| k.radius())
+        .max(rule.kernel.radius());
+    let padded_width = width + 2 * margin;
+    let padded_height = height + 2 * margin;
+
+    let mut padded = vec![0f32; padded_width * padded_height];
+    let mut next = vec![0f32; area];
+
+    for _ in 0..iterations {
+        for y in 0..height {
+            let src_off = y * width;
+            let dst_off = (y + margin) * padded_width + margin;
+            padded[dst_off..dst_off + width]
+                .copy_from_slice(&curr[src_off..src_off + width]);
+        }
+
+        let padded = &padded;
+        next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            let row_off = (y + margin) * padded_width + margin;
+            for (x, out) in row.iter_mut().enumerate() {
+                let idx = row_off + x;
+                let cell = padded[idx];
+                let n = convolve(padded, padded_width, idx, &rule.kernel);
+                let m = rule
+                    .inner
+                    .as_ref()
+                    .map_or(cell, |k| convolve(padded, padded_width, idx, k));
+                *out = rule.growth.apply(cell, n, m, rule.dt);
+            }
+        });
+        std::mem::swap(&mut curr, &mut next);
+    }
+
+    curr
+}
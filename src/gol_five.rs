@@ -1,15 +1,24 @@
-use std::{sync::Arc, thread};
+use std::{ops::ControlFlow, sync::Arc, thread};
 
-fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
+use crate::utils::{mask::BitMask, rule::Rule, schedule::Schedule};
+
+fn compute_next_generation(
+    board: &[Vec<u64>],
+    width: usize,
+    rule: Rule,
+    mask: Option<&Arc<BitMask>>,
+    background: u8,
+) -> Vec<Vec<u64>>
 {
     let height = board.len();
     let wpr = width / 64;
+    let bg_word = if background != 0 { !0u64 } else { 0 };
 
     let threads = thread::available_parallelism()
         .map(|n| n.get())
         .unwrap_or(1);
 
-    let chunk_size = (height + threads - 1) / threads;
+    let chunk_size = height.div_ceil(threads);
 
     let board = Arc::new(board.to_vec());
     let mut handles = Vec::with_capacity(threads.min(height));
@@ -18,16 +27,21 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
         let start = chunk_start;
         let end = (start + chunk_size).min(height);
         let board = Arc::clone(&board);
+        let mask = mask.map(Arc::clone);
 
         let handle = thread::spawn(move || {
             let mut local = Vec::with_capacity(end - start);
             for r in start..end {
-                let prev = if r > 0 { &board[r - 1] } else { &vec![0; wpr] };
+                let prev = if r > 0 {
+                    &board[r - 1]
+                } else {
+                    &vec![bg_word; wpr]
+                };
                 let cur = &board[r];
                 let next = if r + 1 < board.len() {
                     &board[r + 1]
                 } else {
-                    &vec![0; wpr]
+                    &vec![bg_word; wpr]
                 };
 
                 let mut row_out = vec![0u64; wpr];
@@ -42,12 +56,16 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                             count += ((cw >> (bit - 1)) & 1) as u8;
                         } else if wi > 0 {
                             count += ((cur[wi - 1] >> 63) & 1) as u8;
+                        } else {
+                            count += background;
                         }
                         // East
                         if bit < 63 {
                             count += ((cw >> (bit + 1)) & 1) as u8;
                         } else if wi + 1 < wpr {
                             count += ((cur[wi + 1]) & 1) as u8;
+                        } else {
+                            count += background;
                         }
                         // North
                         count += ((prev[wi] >> bit) & 1) as u8;
@@ -56,12 +74,16 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                             count += ((prev[wi] >> (bit - 1)) & 1) as u8;
                         } else if wi > 0 {
                             count += ((prev[wi - 1] >> 63) & 1) as u8;
+                        } else {
+                            count += background;
                         }
                         // North-East
                         if bit < 63 {
                             count += ((prev[wi] >> (bit + 1)) & 1) as u8;
                         } else if wi + 1 < wpr {
                             count += ((prev[wi + 1]) & 1) as u8;
+                        } else {
+                            count += background;
                         }
                         // South
                         count += ((next[wi] >> bit) & 1) as u8;
@@ -70,24 +92,32 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                             count += ((next[wi] >> (bit - 1)) & 1) as u8;
                         } else if wi > 0 {
                             count += ((next[wi - 1] >> 63) & 1) as u8;
+                        } else {
+                            count += background;
                         }
                         // South-East
                         if bit < 63 {
                             count += ((next[wi] >> (bit + 1)) & 1) as u8;
                         } else if wi + 1 < wpr {
                             count += ((next[wi + 1]) & 1) as u8;
+                        } else {
+                            count += background;
                         }
 
                         let alive = ((cw >> bit) & 1) == 1;
                         if alive {
-                            if count == 2 || count == 3 {
+                            if rule.survives(count as usize) {
                                 new_word |= 1 << bit;
                             }
-                        } else if count == 3 {
+                        } else if rule.born(count as usize) {
                             new_word |= 1 << bit;
                         }
                     }
 
+                    if let Some(mask) = &mask {
+                        new_word =
+                            (new_word & mask.keep[r][wi]) | mask.force[r][wi];
+                    }
                     row_out[wi] = new_word;
                 }
 
@@ -118,7 +148,84 @@ pub fn gol_five(
 {
     let mut board = initial;
     for _ in 0..iterations {
-        board = compute_next_generation(&board, width);
+        board = compute_next_generation(&board, width, Rule::conway(), None, 0);
     }
     board
 }
+
+/// Same as gol_five, but generation `g` is computed with
+/// `schedule.rule_at(g)`, and cells pinned by `mask` keep their forced
+/// state. Cells beyond the edge follow the uniform background, which
+/// strobes under B0 rules instead of staying dead.
+pub fn gol_five_scheduled(
+    initial: Vec<Vec<u64>>,
+    width: usize,
+    schedule: &Schedule,
+    mask: Option<&BitMask>,
+    iterations: usize,
+) -> Vec<Vec<u64>>
+{
+    gol_five_resumed(initial, width, schedule, mask, (0, 0), iterations).0
+}
+
+/// Same as gol_five_scheduled, but continuing a run whose `start` is the
+/// generation it has reached and the background state then, as stored in
+/// a checkpoint. Returns the board along with the new background.
+pub fn gol_five_resumed(
+    initial: Vec<Vec<u64>>,
+    width: usize,
+    schedule: &Schedule,
+    mask: Option<&BitMask>,
+    start: (usize, u8),
+    iterations: usize,
+) -> (Vec<Vec<u64>>, u8)
+{
+    let (board, background, _) = gol_five_observed(
+        initial,
+        width,
+        schedule,
+        mask,
+        start,
+        iterations,
+        |_, _, _| ControlFlow::Continue(()),
+    );
+    (board, background)
+}
+
+/// Same as gol_five_resumed, but `observe` sees the generation number,
+/// board and background after every step and can stop the run early.
+/// Returns the generation reached along with the board and background.
+pub fn gol_five_observed<F>(
+    initial: Vec<Vec<u64>>,
+    width: usize,
+    schedule: &Schedule,
+    mask: Option<&BitMask>,
+    start: (usize, u8),
+    iterations: usize,
+    mut observe: F,
+) -> (Vec<Vec<u64>>, u8, usize)
+where
+    F: FnMut(usize, &[Vec<u64>], u8) -> ControlFlow<()>,
+{
+    let mask = mask.map(|m| Arc::new(m.clone()));
+    let (first, mut background) = start;
+    let mut board = initial;
+    if let Some(mask) = &mask {
+        mask.apply(&mut board);
+    }
+    for generation in first..first + iterations {
+        let rule = schedule.rule_at(generation);
+        board = compute_next_generation(
+            &board,
+            width,
+            rule,
+            mask.as_ref(),
+            background,
+        );
+        background = rule.background_next(background);
+        if observe(generation + 1, &board, background).is_break() {
+            return (board, background, generation + 1);
+        }
+    }
+    (board, background, first + iterations)
+}
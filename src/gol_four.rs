@@ -1,4 +1,10 @@
-fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
+use crate::utils::mask::BitMask;
+
+fn compute_next_generation(
+    board: &[Vec<u64>],
+    width: usize,
+    mask: Option<&BitMask>,
+) -> Vec<Vec<u64>>
 {
     let height = board.len();
     let wpr = width / 64;
@@ -29,7 +35,7 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                 if bit < 63 {
                     count += ((cw >> (bit + 1)) & 1) as u8;
                 } else if wi + 1 < wpr {
-                    count += ((cur[wi + 1] >> 0) & 1) as u8;
+                    count += (cur[wi + 1] & 1) as u8;
                 }
                 // North
                 count += ((prev[wi] >> bit) & 1) as u8;
@@ -43,7 +49,7 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                 if bit < 63 {
                     count += ((prev[wi] >> (bit + 1)) & 1) as u8;
                 } else if wi + 1 < wpr {
-                    count += ((prev[wi + 1] >> 0) & 1) as u8;
+                    count += (prev[wi + 1] & 1) as u8;
                 }
                 // South
                 count += ((nxt[wi] >> bit) & 1) as u8;
@@ -57,7 +63,7 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                 if bit < 63 {
                     count += ((nxt[wi] >> (bit + 1)) & 1) as u8;
                 } else if wi + 1 < wpr {
-                    count += ((nxt[wi + 1] >> 0) & 1) as u8;
+                    count += (nxt[wi + 1] & 1) as u8;
                 }
 
                 let alive = ((cw >> bit) & 1) == 1;
@@ -70,6 +76,9 @@ fn compute_next_generation(board: &[Vec<u64>], width: usize) -> Vec<Vec<u64>>
                 }
             }
 
+            if let Some(mask) = mask {
+                new_word = (new_word & mask.keep[r][wi]) | mask.force[r][wi];
+            }
             next[r][wi] = new_word;
         }
     }
@@ -85,7 +94,23 @@ pub fn gol_four(
 {
     let mut board = initial;
     for _ in 0..iterations {
-        board = compute_next_generation(&board, width);
+        board = compute_next_generation(&board, width, None);
+    }
+    board
+}
+
+/// Same as gol_four, but cells pinned by `mask` keep their forced state.
+pub fn gol_four_masked(
+    initial: Vec<Vec<u64>>,
+    width: usize,
+    mask: &BitMask,
+    iterations: usize,
+) -> Vec<Vec<u64>>
+{
+    let mut board = initial;
+    mask.apply(&mut board);
+    for _ in 0..iterations {
+        board = compute_next_generation(&board, width, Some(mask));
     }
     board
 }
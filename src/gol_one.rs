@@ -1,7 +1,7 @@
 pub fn compute_next_generation(current: &[Vec<bool>]) -> Vec<Vec<bool>>
 {
     let height = current.len();
-    let width = current.get(0).map_or(0, |row| row.len());
+    let width = current.first().map_or(0, |row| row.len());
     let mut next = vec![vec![false; width]; height];
 
     for y in 0..height {
@@ -13,19 +13,22 @@ pub fn compute_next_generation(current: &[Vec<bool>]) -> Vec<Vec<bool>>
             let x_end = if x + 1 < width { x + 1 } else { width - 1 };
 
             let mut live_neighbors = 0;
-            for ny in y_start..=y_end {
-                for nx in x_start..=x_end {
-                    if (ny != y || nx != x) && current[ny][nx] {
+            for (ny, row) in
+                current.iter().enumerate().take(y_end + 1).skip(y_start)
+            {
+                for (nx, &cell) in
+                    row.iter().enumerate().take(x_end + 1).skip(x_start)
+                {
+                    if (ny != y || nx != x) && cell {
                         live_neighbors += 1;
                     }
                 }
             }
 
-            next[y][x] = match (current[y][x], live_neighbors) {
-                (true, 2) | (true, 3) => true,
-                (false, 3) => true,
-                _ => false,
-            };
+            next[y][x] = matches!(
+                (current[y][x], live_neighbors),
+                (true, 2) | (true, 3) | (false, 3)
+            );
         }
     }
 
@@ -2,65 +2,168 @@
 //! - input: finite grid as Vec<u8> (row-major, 0=dead, 1=alive)
 //! - width: number of columns
 //! - iterations: number of generations
+//!
 //! returns: Vec<u8> in the same flat format
 
 use rayon::prelude::*;
 
-pub fn gol_three(mut curr: Vec<u8>, width: usize, iterations: usize)
-    -> Vec<u8>
+use crate::utils::{
+    flat_rules::{background_next, FlatRules, Moore, Neighbours},
+    infinite_grid::fill_border,
+    mask::Mask,
+    rule::Rule,
+    schedule::Schedule,
+};
+
+pub fn gol_three(curr: Vec<u8>, width: usize, iterations: usize) -> Vec<u8>
+{
+    let schedule = Schedule::constant(Rule::conway());
+    let rules = FlatRules::Schedule(&schedule);
+    gol_three_with(curr, width, rules, None, iterations)
+}
+
+/// Same as gol_three, but each cell follows the rule `rules` gives it, and
+/// cells pinned by `mask` keep their forced state. Cells beyond the edge
+/// follow the uniform background, which strobes under B0 rules instead of
+/// staying dead.
+pub fn gol_three_with(
+    curr: Vec<u8>,
+    width: usize,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    iterations: usize,
+) -> Vec<u8>
+{
+    run(curr, width, rules, mask, iterations, None)
+}
+
+/// Same as gol_three_with, but `observe` is handed generation 0 and then
+/// each generation as soon as it is computed, so a run can be recorded
+/// without keeping its history.
+pub fn gol_three_observed<F>(
+    curr: Vec<u8>,
+    width: usize,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    iterations: usize,
+    mut observe: F,
+) -> Vec<u8>
+where
+    F: FnMut(usize, &[u8]),
+{
+    run(curr, width, rules, mask, iterations, Some(&mut observe))
+}
+
+type Observer<'a> = &'a mut dyn FnMut(usize, &[u8]);
+
+fn run(
+    curr: Vec<u8>,
+    width: usize,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    iterations: usize,
+    observe: Option<Observer>,
+) -> Vec<u8>
+{
+    let padded_width = width + 2 * rules.margin();
+    match rules {
+        FlatRules::Weighted(neighbourhood) => {
+            let kernel = neighbourhood.compile(padded_width);
+            run_counting(curr, width, rules, mask, iterations, observe, &kernel)
+        }
+        _ => {
+            let moore = Moore::new(padded_width);
+            run_counting(curr, width, rules, mask, iterations, observe, &moore)
+        }
+    }
+}
+
+fn run_counting<N: Neighbours>(
+    mut curr: Vec<u8>,
+    width: usize,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    iterations: usize,
+    mut observe: Option<Observer>,
+    neighbours: &N,
+) -> Vec<u8>
 {
     let area = curr.len();
     let height = area / width;
+    let regions = rules.regions();
+    debug_assert!(regions.is_none_or(|r| r.len() == area));
+    debug_assert!(mask.is_none_or(|m| m.keep.len() == area));
+    let margin = rules.margin();
 
-    let padded_width = width + 2;
-    let padded_height = height + 2;
+    let padded_width = width + 2 * margin;
+    let padded_height = height + 2 * margin;
 
     let mut padded_curr = vec![0u8; padded_width * padded_height];
     let mut padded_next = vec![0u8; padded_width * padded_height];
 
+    if let Some(mask) = mask {
+        mask.apply(&mut curr);
+    }
     for y in 0..height {
         let src_off = y * width;
-        let dst_off = (y + 1) * padded_width + 1;
+        let dst_off = (y + margin) * padded_width + margin;
         padded_curr[dst_off..dst_off + width]
             .copy_from_slice(&curr[src_off..src_off + width]);
     }
 
-    let pw = padded_width as isize;
-    let offsets: [isize; 8] =
-        [-(pw + 1), -pw, -(pw - 1), -1, 1, pw - 1, pw, pw + 1];
+    let kernel = rules.kernel(padded_width);
+    let kernel = &kernel;
+    let stride = 2 * kernel.span;
 
-    for _ in 0..iterations {
+    if let Some(observe) = observe.as_mut() {
+        observe(0, &curr);
+    }
+    let mut background = 0u8;
+    for generation in 0..iterations {
+        let tables = rules.tables(kernel, generation);
+        let tables = &tables;
         let curr_addr = padded_curr.as_ptr() as usize;
         let next_addr = padded_next.as_mut_ptr() as usize;
 
-        (1..=height).into_par_iter().for_each(move |y| {
-            let curr_ptr = curr_addr as *const u8;
-            let next_ptr = next_addr as *mut u8;
-            let row_off = (y * padded_width) as isize;
-            for x in 1..=width {
-                let idx = row_off + (x as isize);
-                let mut cnt: u8 = 0;
-                unsafe {
-                    for &off in &offsets {
-                        cnt += *curr_ptr.offset(idx + off);
+        (margin..height + margin)
+            .into_par_iter()
+            .for_each(move |y| {
+                let curr_ptr = curr_addr as *const u8;
+                let next_ptr = next_addr as *mut u8;
+                let row_off = (y * padded_width) as isize;
+                let cell_off = (y - margin) * width;
+                for x in margin..width + margin {
+                    let idx = row_off + (x as isize);
+                    let i = cell_off + x - margin;
+                    let table = regions.map_or(0, |r| r[i] as usize) * stride;
+                    unsafe {
+                        let sum = neighbours.sum(curr_ptr, idx);
+                        let cell = *curr_ptr.offset(idx) as usize;
+                        let val = tables[table + cell * kernel.span + sum];
+                        *next_ptr.offset(idx) = match mask {
+                            Some(mask) => (val & mask.keep[i]) | mask.force[i],
+                            None => val,
+                        };
                     }
-                    let cell = *curr_ptr.offset(idx);
-                    let val = if (cell == 1 && (cnt == 2 || cnt == 3))
-                        || (cell == 0 && cnt == 3)
-                    {
-                        1
-                    } else {
-                        0
-                    };
-                    *next_ptr.offset(idx) = val;
                 }
-            }
-        });
+            });
+        background = background_next(kernel, tables, background);
+        fill_border(&mut padded_next, padded_width, margin, background);
         std::mem::swap(&mut padded_curr, &mut padded_next);
+
+        if let Some(observe) = observe.as_mut() {
+            for y in 0..height {
+                let src_off = (y + margin) * padded_width + margin;
+                let dst_off = y * width;
+                curr[dst_off..dst_off + width]
+                    .copy_from_slice(&padded_curr[src_off..src_off + width]);
+            }
+            observe(generation + 1, &curr);
+        }
     }
 
     for y in 0..height {
-        let src_off = (y + 1) * padded_width + 1;
+        let src_off = (y + margin) * padded_width + margin;
         let dst_off = y * width;
         curr[dst_off..dst_off + width]
             .copy_from_slice(&padded_curr[src_off..src_off + width]);
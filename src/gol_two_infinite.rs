@@ -1,10 +1,17 @@
-use crate::utils::infinite_grid::extend_flat_grid_to_square64;
+use crate::utils::{
+    infinite_grid::extend_flat_grid_to_square64, rule::Rule, schedule::Schedule,
+};
 
-fn gol_two_step(curr: Vec<u8>, width: usize) -> Vec<u8>
+fn gol_two_step(
+    curr: Vec<u8>,
+    width: usize,
+    table: &[u8; 18],
+    background: u8,
+) -> Vec<u8>
 {
     let height = width;
     let padded_width = width + 2;
-    let mut padded_curr = vec![0u8; padded_width * padded_width];
+    let mut padded_curr = vec![background; padded_width * padded_width];
     let mut padded_next = vec![0u8; padded_width * padded_width];
 
     for y in 0..height {
@@ -27,14 +34,8 @@ fn gol_two_step(curr: Vec<u8>, width: usize) -> Vec<u8>
                     cnt += *ptr.offset(idx + o);
                 }
                 let cell = *ptr.offset(idx);
-                let val = if (cell == 1 && (cnt == 2 || cnt == 3))
-                    || (cell == 0 && cnt == 3)
-                {
-                    1
-                } else {
-                    0
-                };
-                *padded_next.as_mut_ptr().offset(idx) = val;
+                *padded_next.as_mut_ptr().offset(idx) =
+                    table[cell as usize * 9 + cnt as usize];
             }
         }
     }
@@ -49,21 +50,41 @@ fn gol_two_step(curr: Vec<u8>, width: usize) -> Vec<u8>
 }
 
 pub fn gol_two_infinite(
+    grid: Vec<u8>,
+    width: usize,
+    iterations: usize,
+) -> (Vec<u8>, usize)
+{
+    let schedule = Schedule::constant(Rule::conway());
+    let (grid, width, _) =
+        gol_two_infinite_scheduled(grid, width, &schedule, iterations);
+    (grid, width)
+}
+
+/// Same as gol_two_infinite, but generation `g` is computed with
+/// `schedule.rule_at(g)`. The returned byte is the state of every cell
+/// outside the grid, which strobes under B0 rules.
+pub fn gol_two_infinite_scheduled(
     mut grid: Vec<u8>,
     mut width: usize,
+    schedule: &Schedule,
     iterations: usize,
-) -> (Vec<u8>, usize)
+) -> (Vec<u8>, usize, u8)
 {
     let mut aux = Vec::with_capacity(width * width);
-    for _ in 0..iterations {
-        let new_w = extend_flat_grid_to_square64(&grid, width, &mut aux);
+    let mut background = 0u8;
+    for generation in 0..iterations {
+        let new_w =
+            extend_flat_grid_to_square64(&grid, width, background, &mut aux);
 
         if new_w != width {
             std::mem::swap(&mut aux, &mut grid);
             width = new_w;
         }
 
-        grid = gol_two_step(grid, width);
+        let rule = schedule.rule_at(generation);
+        grid = gol_two_step(grid, width, &rule.table(), background);
+        background = rule.background_next(background);
     }
-    (grid, width)
+    (grid, width, background)
 }
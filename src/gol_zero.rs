@@ -49,22 +49,21 @@ impl<'a> CellContext<'a>
     fn next_state(&self) -> bool
     {
         let live_neighbors = self.count_live_neighbors();
-        match (self.grid[self.row][self.col], live_neighbors) {
-            (true, 2) | (true, 3) => true,
-            (false, 3) => true,
-            _ => false,
-        }
+        matches!(
+            (self.grid[self.row][self.col], live_neighbors),
+            (true, 2) | (true, 3) | (false, 3)
+        )
     }
 }
 
 pub fn compute_next_generation(current: &Grid) -> Grid
 {
     let height = current.len();
-    let width = current.get(0).map_or(0, |r| r.len());
+    let width = current.first().map_or(0, |r| r.len());
     let mut next = vec![vec![false; width]; height];
 
-    for row in 0..height {
-        for col in 0..width {
+    for (row, next_row) in next.iter_mut().enumerate() {
+        for (col, cell) in next_row.iter_mut().enumerate() {
             let ctx = CellContext {
                 grid: current,
                 row,
@@ -72,7 +71,7 @@ pub fn compute_next_generation(current: &Grid) -> Grid
                 height,
                 width,
             };
-            next[row][col] = ctx.next_state();
+            *cell = ctx.next_state();
         }
     }
 
@@ -0,0 +1,11 @@
+pub mod gol_colour;
+pub mod gol_continuous;
+pub mod gol_five;
+pub mod gol_four;
+pub mod gol_one;
+pub mod gol_three;
+pub mod gol_three_infinite;
+pub mod gol_two;
+pub mod gol_two_infinite;
+pub mod gol_zero;
+pub mod utils;
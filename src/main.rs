@@ -1,73 +1,1324 @@
 // src/main.rs
 
-use std::env;
-use utils::{
-    benchmark::benchmark,
-    conversion::{flatten_grid, to_bitboards},
-    initialize_grid::{load_grid, parse_arg},
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-mod gol_five;
-mod gol_four;
-mod gol_one;
-mod gol_three;
-mod gol_three_infinite;
-mod gol_two;
-mod gol_two_infinite;
-mod gol_zero;
-mod utils;
-
-use crate::{
-    gol_five::gol_five,
-    gol_four::gol_four,
+use game_of_life::{
+    gol_colour::{gol_colour, ColourVariant},
+    gol_continuous::{gol_continuous, ContinuousRule},
+    gol_five::{gol_five, gol_five_observed, gol_five_scheduled},
+    gol_four::{gol_four, gol_four_masked},
     gol_one::gol_one,
-    gol_three::gol_three,
-    gol_three_infinite::gol_three_infinite,
-    gol_two::gol_two,
-    gol_two_infinite::gol_two_infinite,
+    gol_three::{gol_three, gol_three_observed, gol_three_with},
+    gol_three_infinite::{gol_three_infinite, gol_three_infinite_scheduled},
+    gol_two::{gol_two, gol_two_with},
+    gol_two_infinite::{gol_two_infinite, gol_two_infinite_scheduled},
     gol_zero::gol_zero,
+    utils::{
+        ages::AgeMap,
+        apgcode::encode_apgcode,
+        benchmark::benchmark,
+        checkpoint::{
+            is_checkpoint_file, read_checkpoint, write_checkpoint, Checkpoint,
+        },
+        conversion::{
+            flatten_colour_grid, flatten_grid, from_bitboards, to_bitboards,
+            unflatten_grid,
+        },
+        error::LoadError,
+        flat_rules::FlatRules,
+        gif::{FrameRange, GifOptions, GifWriter},
+        history::{is_history_file, HistoryReader, HistoryWriter},
+        initialize_grid::{
+            grid_size, load_colour_grid, load_mask, load_pattern,
+            load_rule_map, parse_arg, prepare_pattern, read_pattern,
+            LoadOptions,
+        },
+        mask::Mask,
+        neighbourhood::load_neighbourhood,
+        netpbm::{write_cells_image, write_pgm, ImageFormat},
+        palette::{parse_colour, Palette},
+        pattern::{format_pattern, write_pattern, Pattern, PatternFormat},
+        png::{write_png, PngOptions, Region},
+        rule::Rule,
+        schedule::{load_schedule, Schedule},
+        snapshots::{SnapshotOptions, Snapshots},
+        svg::{write_svg, SvgOptions},
+        transform::Transform,
+        y4m::{Y4mOptions, Y4mWriter},
+    },
 };
 
-fn main() {
-    // Parse arguments
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <initial_state_file> <iterations>", args[0]);
+struct Options
+{
+    file_path: String,
+    iterations: usize,
+    continuous: Option<String>,
+    pgm_path: Option<String>,
+    colours: Option<String>,
+    rule_map_path: Option<String>,
+    schedule_path: Option<String>,
+    mask_path: Option<String>,
+    neighbourhood_path: Option<String>,
+    output_path: Option<String>,
+    output_format: Option<PatternFormat>,
+    apgcode: bool,
+    load: LoadOptions,
+    image_path: Option<String>,
+    ages: bool,
+    cell_size: Option<usize>,
+    palette: Palette,
+    png_path: Option<String>,
+    grid_lines: Option<[u8; 3]>,
+    region: Option<Region>,
+    gif_path: Option<String>,
+    delay: u16,
+    stride: usize,
+    frames: Option<FrameRange>,
+    y4m_path: Option<String>,
+    fps: u32,
+    svg_path: Option<String>,
+    labels: bool,
+    label_every: Option<usize>,
+    highlight_objects: bool,
+    checkpoint_path: Option<String>,
+    snapshot_dir: Option<String>,
+    snapshots: SnapshotOptions,
+    history_path: Option<String>,
+    keyframe_every: usize,
+}
+
+/// Set when an output goes to stdout, which then carries nothing else.
+static STDOUT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Prints a timing or progress line, on stderr if stdout is taken.
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if STDOUT_TAKEN.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Reports a bad argument or input file and exits.
+fn or_exit<T>(result: Result<T, LoadError>) -> T
+{
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
         std::process::exit(1);
+    })
+}
+
+fn exit_with(message: String) -> !
+{
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
+
+fn parse_options(args: &[String]) -> Options
+{
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: {} <initial_state_file|-> <iterations> \
+             [--continuous <smoothlife|lenia>] [--pgm <output_file>] \
+             [--colours <immigration|quadlife>] [--rule-map <map_file>] \
+             [--schedule <schedule_file>] [--mask <mask_file>] \
+             [--neighbourhood <kernel_file>] [--output <output_file|->] \
+             [--format <text|rle|cells|life105|life106|mc>] [--apgcode] \
+             [--alphabet <dead><live>...] [--threshold <0-255>] \
+             [--image <output.pbm|pgm|ppm>] [--ages] [--scale <pixels>] \
+             [--cell-size <pixels>] [--palette <RRGGBB,...>] \
+             [--png <output_file>] \
+             [--grid-lines <RRGGBB>] [--region <x,y,width,height>] \
+             [--gif <output_file>] [--delay <centiseconds>] [--stride <n>] \
+             [--frames <first>..<last>] [--y4m <output_file|->] [--fps <n>] \
+             [--svg <output_file>] [--labels] [--label-every <n>] \
+             [--highlight-objects] \
+             [--checkpoint <output_file>] [--snapshot-dir <dir>] \
+             [--snapshot-every <generations>] [--snapshot-seconds <s>] \
+             [--keep-snapshots <n>] [--history <output_file|->] \
+             [--keyframe-every <generations>]\n       \
+             {} transform <pattern_file|-> <op>... \
+             (run without ops for details)",
+            args[0], args[0]
+        );
+        std::process::exit(1);
+    }
+
+    let mut opts = Options {
+        file_path: or_exit(parse_arg(args, 1, "initial_state_file")),
+        iterations: or_exit(parse_arg(args, 2, "iterations")),
+        continuous: None,
+        pgm_path: None,
+        colours: None,
+        rule_map_path: None,
+        schedule_path: None,
+        mask_path: None,
+        neighbourhood_path: None,
+        output_path: None,
+        output_format: None,
+        apgcode: false,
+        load: LoadOptions::default(),
+        image_path: None,
+        ages: false,
+        cell_size: None,
+        palette: Palette::default(),
+        png_path: None,
+        grid_lines: None,
+        region: None,
+        gif_path: None,
+        delay: 10,
+        stride: 1,
+        frames: None,
+        y4m_path: None,
+        fps: 25,
+        svg_path: None,
+        labels: false,
+        label_every: None,
+        highlight_objects: false,
+        checkpoint_path: None,
+        snapshot_dir: None,
+        snapshots: SnapshotOptions::default(),
+        history_path: None,
+        keyframe_every: 100,
+    };
+
+    let mut i = 3;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--continuous" => {
+                opts.continuous =
+                    Some(or_exit(parse_arg(args, i + 1, "continuous")))
+            }
+            "--pgm" => {
+                opts.pgm_path = Some(or_exit(parse_arg(args, i + 1, "pgm")))
+            }
+            "--colours" => {
+                opts.colours = Some(or_exit(parse_arg(args, i + 1, "colours")))
+            }
+            "--rule-map" => {
+                opts.rule_map_path =
+                    Some(or_exit(parse_arg(args, i + 1, "rule-map")))
+            }
+            "--schedule" => {
+                opts.schedule_path =
+                    Some(or_exit(parse_arg(args, i + 1, "schedule")))
+            }
+            "--mask" => {
+                opts.mask_path = Some(or_exit(parse_arg(args, i + 1, "mask")))
+            }
+            "--neighbourhood" => {
+                opts.neighbourhood_path =
+                    Some(or_exit(parse_arg(args, i + 1, "neighbourhood")))
+            }
+            "--alphabet" => {
+                opts.load.alphabet =
+                    Some(or_exit(parse_arg(args, i + 1, "alphabet")))
+            }
+            "--threshold" => {
+                opts.load.threshold =
+                    or_exit(parse_arg(args, i + 1, "threshold"))
+            }
+            "--scale" => {
+                opts.load.scale = or_exit(parse_arg(args, i + 1, "scale"))
+            }
+            "--image" => {
+                opts.image_path = Some(or_exit(parse_arg(args, i + 1, "image")))
+            }
+            "--cell-size" => {
+                opts.cell_size =
+                    Some(or_exit(parse_arg(args, i + 1, "cell-size")))
+            }
+            "--png" => {
+                opts.png_path = Some(or_exit(parse_arg(args, i + 1, "png")))
+            }
+            "--region" => {
+                opts.region = Some(or_exit(parse_arg(args, i + 1, "region")))
+            }
+            "--grid-lines" => {
+                let colour: String =
+                    or_exit(parse_arg(args, i + 1, "grid-lines"));
+                opts.grid_lines =
+                    Some(or_exit(parse_colour(&colour).map_err(|message| {
+                        LoadError::InvalidArgument {
+                            name: "grid-lines".to_string(),
+                            value: colour,
+                            message,
+                        }
+                    })));
+            }
+            "--gif" => {
+                opts.gif_path = Some(or_exit(parse_arg(args, i + 1, "gif")))
+            }
+            "--delay" => opts.delay = or_exit(parse_arg(args, i + 1, "delay")),
+            "--stride" => {
+                opts.stride =
+                    or_exit(parse_arg::<usize>(args, i + 1, "stride")).max(1)
+            }
+            "--frames" => {
+                opts.frames = Some(or_exit(parse_arg(args, i + 1, "frames")))
+            }
+            "--y4m" => {
+                opts.y4m_path = Some(or_exit(parse_arg(args, i + 1, "y4m")))
+            }
+            "--fps" => opts.fps = or_exit(parse_arg(args, i + 1, "fps")),
+            "--svg" => {
+                opts.svg_path = Some(or_exit(parse_arg(args, i + 1, "svg")))
+            }
+            "--label-every" => {
+                opts.label_every =
+                    Some(or_exit(parse_arg(args, i + 1, "label-every")))
+            }
+            "--checkpoint" => {
+                opts.checkpoint_path =
+                    Some(or_exit(parse_arg(args, i + 1, "checkpoint")))
+            }
+            "--snapshot-dir" => {
+                opts.snapshot_dir =
+                    Some(or_exit(parse_arg(args, i + 1, "snapshot-dir")))
+            }
+            "--snapshot-every" => {
+                opts.snapshots.every =
+                    Some(or_exit(parse_arg(args, i + 1, "snapshot-every")))
+            }
+            "--snapshot-seconds" => {
+                opts.snapshots.interval = Some(Duration::from_secs(or_exit(
+                    parse_arg(args, i + 1, "snapshot-seconds"),
+                )))
+            }
+            "--keep-snapshots" => {
+                opts.snapshots.keep =
+                    or_exit(parse_arg(args, i + 1, "keep-snapshots"))
+            }
+            "--history" => {
+                opts.history_path =
+                    Some(or_exit(parse_arg(args, i + 1, "history")))
+            }
+            "--keyframe-every" => {
+                opts.keyframe_every =
+                    or_exit(parse_arg(args, i + 1, "keyframe-every"))
+            }
+            "--palette" => {
+                opts.palette = or_exit(parse_arg(args, i + 1, "palette"))
+            }
+            "--output" => {
+                opts.output_path =
+                    Some(or_exit(parse_arg(args, i + 1, "output")))
+            }
+            "--format" => {
+                let name: String = or_exit(parse_arg(args, i + 1, "format"));
+                opts.output_format = Some(or_exit(
+                    PatternFormat::from_name(&name)
+                        .ok_or(LoadError::UnknownFormat { name }),
+                ));
+            }
+            "--apgcode" => {
+                opts.apgcode = true;
+                i += 1;
+                continue;
+            }
+            "--ages" => {
+                opts.ages = true;
+                i += 1;
+                continue;
+            }
+            "--labels" => {
+                opts.labels = true;
+                i += 1;
+                continue;
+            }
+            "--highlight-objects" => {
+                opts.highlight_objects = true;
+                i += 1;
+                continue;
+            }
+            other => {
+                eprintln!("Unknown option '{}'", other);
+                std::process::exit(1);
+            }
+        }
+        i += 2;
     }
+    opts
+}
 
-    let file_path: String = parse_arg(&args, 1, "initial_state_file");
-    let iterations: usize = parse_arg(&args, 2, "iterations");
+/// Turns away options that the run the others select has no engine for,
+/// rather than quietly ignoring them.
+fn check_combinations(opts: &Options)
+{
+    let outputs = [
+        ("--output", &opts.output_path),
+        ("--gif", &opts.gif_path),
+        ("--y4m", &opts.y4m_path),
+        ("--history", &opts.history_path),
+    ];
+    let to_stdout: Vec<&str> = outputs
+        .iter()
+        .filter(|(_, path)| path.as_deref() == Some("-"))
+        .map(|(flag, _)| *flag)
+        .collect();
+    if let [first, second, ..] = to_stdout[..] {
+        exit_with(format!(
+            "{} and {} cannot both write to stdout",
+            first, second
+        ));
+    }
+    if opts.ages && opts.image_path.is_none() {
+        exit_with("--ages needs --image".to_string());
+    }
+    if opts.pgm_path.is_some() && opts.continuous.is_none() {
+        exit_with("--pgm needs --continuous".to_string());
+    }
+    let given = |flag, path: &Option<String>| path.as_ref().map(|_| flag);
+    let schedule = given("--schedule", &opts.schedule_path);
+    let rule_map = given("--rule-map", &opts.rule_map_path);
+    let neighbourhood = given("--neighbourhood", &opts.neighbourhood_path);
+    let mask = given("--mask", &opts.mask_path);
+    let checkpoint = given("--checkpoint", &opts.checkpoint_path);
+    let snapshots = given("--snapshot-dir", &opts.snapshot_dir);
+    let gif = given("--gif", &opts.gif_path);
+    let y4m = given("--y4m", &opts.y4m_path);
+    let history = given("--history", &opts.history_path);
+    let ages = opts.ages.then_some("--ages");
+    let output = given("--output", &opts.output_path);
+    let image = given("--image", &opts.image_path);
+    let png = given("--png", &opts.png_path);
+    let svg = given("--svg", &opts.svg_path);
+    let colours = given("--colours", &opts.colours);
+    let apgcode = opts.apgcode.then_some("--apgcode");
+    let recording = gif.or(y4m).or(history).or(ages);
 
-    // Load & preprocess
-    let initial_grid = load_grid(&file_path);
+    let (run, unsupported) = if opts.continuous.is_some() {
+        // A continuous field only ever goes to --pgm.
+        (
+            "with --continuous".to_string(),
+            vec![
+                schedule,
+                rule_map,
+                neighbourhood,
+                mask,
+                ages,
+                colours,
+                output,
+                image,
+                png,
+                svg,
+                apgcode,
+                gif,
+                y4m,
+                history,
+                checkpoint,
+                snapshots,
+            ],
+        )
+    } else if opts.colours.is_some() {
+        // gol_colour is neither observed nor checkpointed.
+        (
+            "with --colours".to_string(),
+            vec![
+                schedule,
+                rule_map,
+                neighbourhood,
+                mask,
+                ages,
+                gif,
+                y4m,
+                history,
+                checkpoint,
+                snapshots,
+            ],
+        )
+    } else if is_history_file(&opts.file_path) {
+        (
+            "when replaying a history".to_string(),
+            vec![schedule, rule_map, neighbourhood, mask, ages],
+        )
+    } else if is_checkpoint_file(&opts.file_path) {
+        // A checkpoint records a single rule and no mask.
+        (
+            "when resuming a checkpoint".to_string(),
+            vec![rule_map, neighbourhood, mask, ages],
+        )
+    } else if let Some(flag) = rule_map.or(neighbourhood) {
+        // These bring their own rules, and only the flat engines take them.
+        let other = if rule_map.is_some() {
+            neighbourhood
+        } else {
+            None
+        };
+        (
+            format!("with {}", flag),
+            vec![schedule, other, checkpoint, snapshots],
+        )
+    } else if let Some(flag) = recording {
+        // Recording runs on gol_three, checkpoints on gol_five.
+        (format!("with {}", flag), vec![checkpoint, snapshots])
+    } else if let Some(flag) = mask {
+        // A checkpoint stores no mask, so it could not be resumed.
+        (format!("with {}", flag), vec![checkpoint, snapshots])
+    } else {
+        return;
+    };
+    if let Some(flag) = unsupported.into_iter().flatten().next() {
+        exit_with(format!("{} cannot be used {}", flag, run));
+    }
+}
+
+fn run_continuous(opts: &Options, kind: &str)
+{
+    let rule = match kind {
+        "smoothlife" => ContinuousRule::smoothlife(),
+        "lenia" => ContinuousRule::lenia(),
+        other => {
+            eprintln!("Unknown continuous rule '{}'", other);
+            std::process::exit(1);
+        }
+    };
+    let (initial_grid, _) = or_exit(load_pattern(&opts.file_path, &opts.load));
     let width = initial_grid[0].len();
-    let flat_grid = flatten_grid(&initial_grid);
-    let (bitboards, _bb_width) = to_bitboards(&initial_grid);
+    let field: Vec<f32> = flatten_grid(&initial_grid)
+        .iter()
+        .map(|&c| c as f32)
+        .collect();
+
+    let (field, elapsed) =
+        benchmark(&field, |f| gol_continuous(f, width, &rule, opts.iterations));
+    report!(
+        "Simulation with gol_continuous ({}) took {:?}",
+        kind,
+        elapsed
+    );
+
+    if let Some(path) = &opts.pgm_path {
+        write_pgm(path, &field, width).unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        });
+    }
+}
+
+fn run_colours(opts: &Options, kind: &str) -> Vec<Vec<u8>>
+{
+    let variant = match kind {
+        "immigration" => ColourVariant::Immigration,
+        "quadlife" => ColourVariant::QuadLife,
+        other => {
+            eprintln!("Unknown colour variant '{}'", other);
+            std::process::exit(1);
+        }
+    };
+    let colour_grid = or_exit(load_colour_grid(
+        &opts.file_path,
+        &opts.load,
+        variant.colours(),
+    ));
+    let width = colour_grid[0].len();
+    let flat = flatten_colour_grid(&colour_grid);
+
+    let (flat, elapsed) = benchmark(&flat, |flat| {
+        gol_colour(
+            flat,
+            width,
+            variant,
+            opts.iterations,
+            |generation, population| {
+                report!(
+                    "Generation {}: population per colour {:?}",
+                    generation,
+                    population
+                );
+            },
+        )
+    });
+    report!("Simulation with gol_colour ({}) took {:?}", kind, elapsed);
+    unflatten_grid(&flat, width)
+}
+
+/// Runs the flat engines, the only ones that take per-cell rules and
+/// weighted neighbourhoods.
+fn run_flat(
+    opts: &Options,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    flat_grid: &Vec<u8>,
+    width: usize,
+) -> Vec<Vec<u8>>
+{
+    let iterations = opts.iterations;
+    let kind = match (rules, mask) {
+        (FlatRules::Map(_), None) => "rule map",
+        (FlatRules::Map(_), Some(_)) => "rule map, masked",
+        (_, None) => "weighted",
+        (_, Some(_)) => "weighted, masked",
+    };
+
+    let (_, elapsed) = benchmark(flat_grid, |flat| {
+        gol_two_with(flat, width, rules, mask, iterations)
+    });
+    report!(
+        "Simulation with gol_two_with (flat, {}) took {:?}",
+        kind,
+        elapsed
+    );
+
+    let (flat, elapsed) = benchmark(flat_grid, |flat| {
+        gol_three_with(flat, width, rules, mask, iterations)
+    });
+    report!(
+        "Simulation with gol_three_with (flat, {}) took {:?}",
+        kind,
+        elapsed
+    );
+    unflatten_grid(&flat, width)
+}
+
+/// Opens an output stream; `-` is stdout.
+fn create_output(path: &str) -> Box<dyn Write>
+{
+    if path == "-" {
+        return Box::new(BufWriter::new(io::stdout()));
+    }
+    let file = File::create(path).unwrap_or_else(|e| {
+        eprintln!("Failed to create {}: {}", path, e);
+        std::process::exit(1);
+    });
+    Box::new(BufWriter::new(file))
+}
+
+fn or_exit_writing<T>(path: &str, result: io::Result<T>) -> T
+{
+    result.unwrap_or_else(|e| {
+        eprintln!("Failed to write {}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+/// `transform <pattern_file|-> <op>...`: applies the transformations in
+/// order and writes the result, RLE on stdout unless told otherwise.
+fn run_transform(args: &[String])
+{
+    if args.len() < 3 {
+        eprintln!(
+            "Usage: {} transform <pattern_file|-> <op>... \
+             [--output <output_file|->] \
+             [--format <text|rle|cells|life105|life106|mc>] \
+             [--alphabet <dead><live>...]\n\
+             ops: rotate:<90|180|270> reflect:<x|y> translate:<dx>,<dy> crop \
+             pad:<n> pad:<top>,<right>,<bottom>,<left> tile:<across>x<down> \
+             scale:<n>",
+            args[0]
+        );
+        std::process::exit(1);
+    }
+
+    let path: String = or_exit(parse_arg(args, 2, "pattern_file"));
+    let mut load = LoadOptions::default();
+    let mut ops = Vec::new();
+    let mut output_path = "-".to_string();
+    let mut output_format = None;
+    let mut i = 3;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--output" => {
+                output_path = or_exit(parse_arg(args, i + 1, "output"))
+            }
+            "--alphabet" => {
+                load.alphabet =
+                    Some(or_exit(parse_arg(args, i + 1, "alphabet")))
+            }
+            "--format" => {
+                let name: String = or_exit(parse_arg(args, i + 1, "format"));
+                output_format = Some(or_exit(
+                    PatternFormat::from_name(&name)
+                        .ok_or(LoadError::UnknownFormat { name }),
+                ));
+            }
+            _ => {
+                ops.push(or_exit(parse_arg::<Transform>(
+                    args,
+                    i,
+                    "transformation",
+                )));
+                i += 1;
+                continue;
+            }
+        }
+        i += 2;
+    }
+
+    let pattern = or_exit(ops.iter().try_fold(
+        or_exit(read_pattern(&path, &load)),
+        |pattern, op| {
+            op.apply(&pattern).map_err(|e| LoadError::invalid(&path, e))
+        },
+    ));
+    let format = output_format
+        .or_else(|| PatternFormat::from_extension(&output_path))
+        .unwrap_or(PatternFormat::Rle);
+    let result = if output_path == "-" {
+        io::stdout()
+            .lock()
+            .write_all(format_pattern(&pattern, format).as_bytes())
+    } else {
+        write_pattern(&output_path, &pattern, format)
+    };
+    or_exit_writing(&output_path, result);
+}
+
+/// Runs gol_three under `rules`, streaming the selected generations to
+/// an animated GIF and/or a Y4M video as they are computed. A history file
+/// gets every generation, regardless of --frames and --stride, and so does
+/// the age map that --ages asks for, which is returned alongside the state.
+fn run_recorded(
+    opts: &Options,
+    rules: FlatRules,
+    mask: Option<&Mask>,
+    flat_grid: &Vec<u8>,
+    width: usize,
+) -> (Vec<Vec<u8>>, Option<Vec<Vec<u8>>>)
+{
+    let height = flat_grid.len() / width;
+    let mut gif = opts.gif_path.as_ref().map(|path| {
+        let options = GifOptions {
+            region: opts.region,
+            cell_size: opts.cell_size.unwrap_or(1),
+            delay: opts.delay,
+            palette: opts.palette.clone(),
+        };
+        (
+            path,
+            or_exit_writing(
+                path,
+                GifWriter::new(create_output(path), width, height, &options),
+            ),
+        )
+    });
+    let mut y4m = opts.y4m_path.as_ref().map(|path| {
+        let options = Y4mOptions {
+            region: opts.region,
+            cell_size: opts.cell_size.unwrap_or(1),
+            fps: opts.fps,
+            palette: opts.palette.clone(),
+        };
+        (
+            path,
+            or_exit_writing(
+                path,
+                Y4mWriter::new(create_output(path), width, height, &options),
+            ),
+        )
+    });
+    let mut history = opts.history_path.as_ref().map(|path| {
+        (
+            path,
+            or_exit_writing(
+                path,
+                HistoryWriter::new(
+                    create_output(path),
+                    width,
+                    height,
+                    opts.keyframe_every,
+                ),
+            ),
+        )
+    });
+    let mut ages = opts.ages.then(|| AgeMap::new(flat_grid.len()));
+    let frames = opts.frames.unwrap_or(FrameRange {
+        first: 0,
+        last: opts.iterations,
+    });
+
+    let mut recorded = 0;
+    let (flat, elapsed) = benchmark(flat_grid, |flat| {
+        gol_three_observed(
+            flat,
+            width,
+            rules,
+            mask,
+            opts.iterations,
+            |generation, cells| {
+                if let Some((path, history)) = &mut history {
+                    or_exit_writing(path, history.add_frame(cells));
+                }
+                if let Some(ages) = &mut ages {
+                    ages.add_generation(cells);
+                }
+                let wanted = generation >= frames.first
+                    && generation <= frames.last
+                    && (generation - frames.first).is_multiple_of(opts.stride);
+                if !wanted {
+                    return;
+                }
+                if let Some((path, gif)) = &mut gif {
+                    or_exit_writing(path, gif.add_frame(cells, width));
+                }
+                if let Some((path, y4m)) = &mut y4m {
+                    or_exit_writing(path, y4m.add_frame(cells, width));
+                }
+                if gif.is_some() || y4m.is_some() {
+                    recorded += 1;
+                }
+            },
+        )
+    });
+    if let Some((path, gif)) = gif {
+        or_exit_writing(path, gif.finish());
+    }
+    if let Some((path, y4m)) = y4m {
+        or_exit_writing(path, y4m.finish());
+    }
+    if let Some((path, history)) = history {
+        or_exit_writing(path, history.finish());
+    }
+
+    report!(
+        "Simulation with gol_three_observed (flat) took {:?}, \
+         recording {} frames",
+        elapsed,
+        recorded
+    );
+    (
+        unflatten_grid(&flat, width),
+        ages.map(|ages| unflatten_grid(&ages.ages, width)),
+    )
+}
+
+/// Reads generation `opts.iterations` back out of a recorded history.
+fn run_replay(opts: &Options) -> Vec<Vec<u8>>
+{
+    let path = &opts.file_path;
+    let mut history = or_exit(HistoryReader::open(path));
+    let width = history.width;
+    let (cells, elapsed) =
+        benchmark(&opts.iterations, |generation| history.frame(generation));
+    let cells = cells.unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+        std::process::exit(1);
+    });
+    let population = cells.iter().filter(|&&c| c != 0).count();
+    report!(
+        "Generation {} of {} (0..{}) read from history in {:?}, population {}",
+        opts.iterations,
+        path,
+        history.len(),
+        elapsed,
+        population
+    );
+    unflatten_grid(&cells, width)
+}
 
-    // Benchmark simulations
-    let (_, elapsed) = benchmark(&initial_grid, |grid| gol_zero(grid, iterations));
-    println!("Simulation with gol_zero took {:?}", elapsed);
+/// Runs gol_five on from `start`, saving snapshots along the way with
+/// --snapshot-dir. At the end, or when interrupted with Ctrl-C, the state
+/// reached goes to the snapshot directory and the --checkpoint file so
+/// that a later run can carry on from there.
+fn run_checkpointed(
+    opts: &Options,
+    schedule: &Schedule,
+    start: Checkpoint,
+) -> Vec<Vec<u8>>
+{
+    let width = start.width;
+    let first = start.generation;
+    let mut snapshots = opts.snapshot_dir.as_ref().map(|dir| {
+        (
+            dir,
+            or_exit_writing(dir, Snapshots::new(dir, opts.snapshots.clone())),
+        )
+    });
+    let checkpoint = |generation, board: &[Vec<u64>], background| Checkpoint {
+        rule: schedule.rule_at(generation),
+        width,
+        generation,
+        origin: start.origin,
+        background,
+        board: board.to_vec(),
+    };
 
-    let (_, elapsed) = benchmark(&initial_grid, |grid| gol_one(grid, iterations));
-    println!("Simulation with gol_one took {:?}", elapsed);
+    // A second Ctrl-C gives up on saving and exits straight away.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupted);
+    if let Err(e) = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    }) {
+        eprintln!("Cannot catch Ctrl-C: {}", e);
+    }
+
+    let ((board, background, generation), elapsed) =
+        benchmark(&start.board, |bb| {
+            gol_five_observed(
+                bb,
+                width,
+                schedule,
+                None,
+                (first, start.background),
+                opts.iterations,
+                |generation, board, background| {
+                    if interrupted.load(Ordering::SeqCst) {
+                        return ControlFlow::Break(());
+                    }
+                    if let Some((dir, snapshots)) = &mut snapshots {
+                        if snapshots.due(generation) {
+                            or_exit_writing(
+                                dir,
+                                snapshots.save(&checkpoint(
+                                    generation, board, background,
+                                )),
+                            );
+                        }
+                    }
+                    ControlFlow::Continue(())
+                },
+            )
+        });
+    report!(
+        "Simulation with gol_five_observed (bitboard) from generation {} \
+         took {:?}",
+        first,
+        elapsed
+    );
+
+    let last = checkpoint(generation, &board, background);
+    if let Some((dir, snapshots)) = &mut snapshots {
+        let path = or_exit_writing(dir, snapshots.save(&last));
+        report!(
+            "Snapshot of generation {} written to {}",
+            generation,
+            path.display()
+        );
+    }
+    if let Some(path) = &opts.checkpoint_path {
+        or_exit_writing(path, write_checkpoint(path, &last));
+    }
+    if interrupted.load(Ordering::SeqCst) {
+        eprintln!("Interrupted at generation {}", generation);
+        std::process::exit(130);
+    }
+    from_bitboards(&board, width)
+}
+
+/// Runs every engine that follows a schedule; the infinite ones have no
+/// edge to pin a mask to, so they sit out masked runs.
+fn run_scheduled(
+    opts: &Options,
+    schedule: &Schedule,
+    mask: Option<&Mask>,
+    flat_grid: &Vec<u8>,
+    bitboards: Vec<Vec<u64>>,
+    width: usize,
+) -> Vec<Vec<u8>>
+{
+    let iterations = opts.iterations;
+    let rules = FlatRules::Schedule(schedule);
+    let bitmask = mask.map(|mask| mask.to_bitmask(width));
+    let kind = if mask.is_some() {
+        "scheduled, masked"
+    } else {
+        "scheduled"
+    };
+
+    let (_, elapsed) = benchmark(flat_grid, |flat| {
+        gol_two_with(flat, width, rules, mask, iterations)
+    });
+    report!(
+        "Simulation with gol_two_with (flat, {}) took {:?}",
+        kind,
+        elapsed
+    );
+
+    if mask.is_none() {
+        let ((_, _, _), elapsed) =
+            benchmark(&(flat_grid.clone(), width), |(flat, w)| {
+                gol_two_infinite_scheduled(flat, w, schedule, iterations)
+            });
+        report!(
+            "Simulation with gol_two_infinite_scheduled (flat) took {:?}",
+            elapsed
+        );
+    }
+
+    let (_, elapsed) = benchmark(flat_grid, |flat| {
+        gol_three_with(flat, width, rules, mask, iterations)
+    });
+    report!(
+        "Simulation with gol_three_with (flat, {}) took {:?}",
+        kind,
+        elapsed
+    );
+
+    if mask.is_none() {
+        let ((_, _, _), elapsed) =
+            benchmark(&(flat_grid.clone(), width), |(flat, w)| {
+                gol_three_infinite_scheduled(flat, w, schedule, iterations)
+            });
+        report!(
+            "Simulation with gol_three_infinite_scheduled (flat) took {:?}",
+            elapsed
+        );
+    }
+
+    let (board, elapsed) = benchmark(&(bitboards, width), |(bb, w)| {
+        gol_five_scheduled(bb, w, schedule, bitmask.as_ref(), iterations)
+    });
+    let kind = if mask.is_some() {
+        "bitboard, masked"
+    } else {
+        "bitboard"
+    };
+    report!(
+        "Simulation with gol_five_scheduled ({}) took {:?}",
+        kind,
+        elapsed
+    );
+    from_bitboards(&board, width)
+}
+
+fn run_masked(
+    opts: &Options,
+    mask: &Mask,
+    flat_grid: &Vec<u8>,
+    bitboards: Vec<Vec<u64>>,
+    width: usize,
+) -> Vec<Vec<u8>>
+{
+    let iterations = opts.iterations;
+    let bitmask = mask.to_bitmask(width);
+    let schedule = Schedule::constant(Rule::conway());
+    let rules = FlatRules::Schedule(&schedule);
+
+    let (_, elapsed) = benchmark(flat_grid, |flat| {
+        gol_two_with(flat, width, rules, Some(mask), iterations)
+    });
+    report!(
+        "Simulation with gol_two_with (flat, masked) took {:?}",
+        elapsed
+    );
+
+    let (_, elapsed) = benchmark(flat_grid, |flat| {
+        gol_three_with(flat, width, rules, Some(mask), iterations)
+    });
+    report!(
+        "Simulation with gol_three_with (flat, masked) took {:?}",
+        elapsed
+    );
+
+    let (_, elapsed) = benchmark(&(bitboards.clone(), width), |(bb, w)| {
+        gol_four_masked(bb, w, &bitmask, iterations)
+    });
+    report!(
+        "Simulation with gol_four_masked (bitboard) took {:?}",
+        elapsed
+    );
+
+    let (board, elapsed) = benchmark(&(bitboards, width), |(bb, w)| {
+        gol_five_scheduled(bb, w, &schedule, Some(&bitmask), iterations)
+    });
+    report!(
+        "Simulation with gol_five_scheduled (bitboard, masked) took {:?}",
+        elapsed
+    );
+    from_bitboards(&board, width)
+}
+
+fn run_benchmarks(
+    opts: &Options,
+    initial_grid: &Vec<Vec<bool>>,
+    flat_grid: &Vec<u8>,
+    bitboards: Vec<Vec<u64>>,
+    width: usize,
+) -> Vec<Vec<u8>>
+{
+    let iterations = opts.iterations;
+
+    let (_, elapsed) =
+        benchmark(initial_grid, |grid| gol_zero(grid, iterations));
+    report!("Simulation with gol_zero took {:?}", elapsed);
+
+    let (_, elapsed) =
+        benchmark(initial_grid, |grid| gol_one(grid, iterations));
+    report!("Simulation with gol_one took {:?}", elapsed);
 
-    let (_, elapsed) = benchmark(&flat_grid, |flat| gol_two(flat, width, iterations));
-    println!("Simulation with gol_two (flat) took {:?}", elapsed);
+    let (_, elapsed) =
+        benchmark(flat_grid, |flat| gol_two(flat, width, iterations));
+    report!("Simulation with gol_two (flat) took {:?}", elapsed);
 
-    let ((_, _), elapsed) = benchmark(&(flat_grid, width), |(flat, w)| gol_two_infinite(flat, w, iterations));
-    println!("Simulation with gol_two_infinite (flat) took {:?}", elapsed);
+    let ((_, _), elapsed) =
+        benchmark(&(flat_grid.clone(), width), |(flat, w)| {
+            gol_two_infinite(flat, w, iterations)
+        });
+    report!("Simulation with gol_two_infinite (flat) took {:?}", elapsed);
 
-    let flat_grid = flatten_grid(&initial_grid);
-    let (_, elapsed) = benchmark(&flat_grid, |flat| gol_three(flat, width, iterations));
-    println!("Simulation with gol_three (flat) took {:?}", elapsed);
+    let (_, elapsed) =
+        benchmark(flat_grid, |flat| gol_three(flat, width, iterations));
+    report!("Simulation with gol_three (flat) took {:?}", elapsed);
 
-    let ((_, _), elapsed) = benchmark(&(flat_grid, width), |(flat, w)| gol_three_infinite(flat, w, iterations));
-    println!("Simulation with gol_three_infinite (flat) took {:?}", elapsed);
+    let ((_, _), elapsed) =
+        benchmark(&(flat_grid.clone(), width), |(flat, w)| {
+            gol_three_infinite(flat, w, iterations)
+        });
+    report!(
+        "Simulation with gol_three_infinite (flat) took {:?}",
+        elapsed
+    );
 
-    let (_, elapsed) = benchmark(&(bitboards.clone(), width), |(bb, w)| gol_four(bb, w, iterations));
-    println!("Simulation with gol_four (bitboard) took {:?}", elapsed);
+    let (_, elapsed) = benchmark(&(bitboards.clone(), width), |(bb, w)| {
+        gol_four(bb, w, iterations)
+    });
+    report!("Simulation with gol_four (bitboard) took {:?}", elapsed);
 
-    let (_, elapsed) = benchmark(&(bitboards, width), |(bb, w)| gol_five(bb, w, iterations));
-    println!("Simulation with gol_five (bitboard) took {:?}", elapsed);
-}
\ No newline at end of file
+    let (board, elapsed) =
+        benchmark(&(bitboards, width), |(bb, w)| gol_five(bb, w, iterations));
+    report!("Simulation with gol_five (bitboard) took {:?}", elapsed);
+    from_bitboards(&board, width)
+}
+
+fn main()
+{
+    // Parse arguments
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("transform") {
+        run_transform(&args);
+        return;
+    }
+    let opts = parse_options(&args);
+    let to_stdout = [
+        &opts.output_path,
+        &opts.gif_path,
+        &opts.y4m_path,
+        &opts.history_path,
+    ]
+    .iter()
+    .any(|path| path.as_deref() == Some("-"));
+    STDOUT_TAKEN.store(to_stdout, Ordering::Relaxed);
+
+    check_combinations(&opts);
+
+    if let Some(kind) = &opts.continuous {
+        run_continuous(&opts, kind);
+        return;
+    }
+
+    let mut age_map = None;
+    let (final_state, rule_name) = if let Some(kind) = &opts.colours {
+        let rule_name = if kind == "quadlife" {
+            "QuadLife"
+        } else {
+            "Immigration"
+        };
+        (run_colours(&opts, kind), Some(rule_name.to_string()))
+    } else if is_history_file(&opts.file_path) {
+        // The iteration count picks the generation to replay.
+        (run_replay(&opts), None)
+    } else if is_checkpoint_file(&opts.file_path) {
+        // Resuming: the saved rule applies unless a schedule overrides it.
+        let start = or_exit(read_checkpoint(&opts.file_path));
+        let schedule = match &opts.schedule_path {
+            Some(path) => or_exit(load_schedule(path)),
+            None => Schedule::constant(start.rule),
+        };
+        let rule_name = schedule
+            .rule_at(start.generation + opts.iterations)
+            .to_string();
+        (run_checkpointed(&opts, &schedule, start), Some(rule_name))
+    } else {
+        // Load & preprocess
+        let pattern = or_exit(read_pattern(&opts.file_path, &opts.load));
+        let size = grid_size(&pattern);
+        let (initial_grid, rule) =
+            or_exit(prepare_pattern(&opts.file_path, pattern));
+        let width = initial_grid[0].len();
+        let flat_grid = flatten_grid(&initial_grid);
+        let (bitboards, _bb_width) = to_bitboards(&initial_grid);
+
+        // A rule named by the pattern file applies unless a schedule
+        // overrides it.
+        let schedule = match (&opts.schedule_path, rule) {
+            (Some(path), _) => Some(or_exit(load_schedule(path))),
+            (None, Some(rule)) if rule != Rule::conway() => {
+                Some(Schedule::constant(rule))
+            }
+            _ => None,
+        };
+        let map = opts
+            .rule_map_path
+            .as_ref()
+            .map(|path| or_exit(load_rule_map(path, size)));
+        let neighbourhood = opts
+            .neighbourhood_path
+            .as_ref()
+            .map(|path| or_exit(load_neighbourhood(path)));
+        let mask = opts
+            .mask_path
+            .as_ref()
+            .map(|path| or_exit(load_mask(path, size)));
+
+        let conway = Schedule::constant(Rule::conway());
+        let rules = match (&map, &neighbourhood) {
+            (Some(map), _) => FlatRules::Map(map),
+            (None, Some(neighbourhood)) => FlatRules::Weighted(neighbourhood),
+            (None, None) => {
+                FlatRules::Schedule(schedule.as_ref().unwrap_or(&conway))
+            }
+        };
+        if let (FlatRules::Map(_) | FlatRules::Weighted(_), Some(schedule)) =
+            (rules, &schedule)
+        {
+            // check_combinations has already turned away --schedule.
+            let flag = if map.is_some() {
+                "--rule-map"
+            } else {
+                "--neighbourhood"
+            };
+            exit_with(format!(
+                "the pattern's rule {} cannot be combined with {}",
+                schedule.rule_at(0),
+                flag
+            ));
+        }
+        // Masked generations are not what the rule alone would give.
+        let rule_name = match rules {
+            FlatRules::Schedule(schedule) if mask.is_none() => {
+                Some(schedule.rule_at(opts.iterations).to_string())
+            }
+            _ => None,
+        };
+
+        let final_state = if opts.gif_path.is_some()
+            || opts.y4m_path.is_some()
+            || opts.history_path.is_some()
+            || opts.ages
+        {
+            let (final_state, ages) =
+                run_recorded(&opts, rules, mask.as_ref(), &flat_grid, width);
+            age_map = ages;
+            final_state
+        } else if opts.checkpoint_path.is_some() || opts.snapshot_dir.is_some()
+        {
+            let schedule = schedule.as_ref().unwrap_or(&conway);
+            let start = Checkpoint {
+                rule: schedule.rule_at(0),
+                width,
+                generation: 0,
+                origin: (0, 0),
+                background: 0,
+                board: bitboards,
+            };
+            run_checkpointed(&opts, schedule, start)
+        } else if map.is_some() || neighbourhood.is_some() {
+            run_flat(&opts, rules, mask.as_ref(), &flat_grid, width)
+        } else if let Some(schedule) = &schedule {
+            run_scheduled(
+                &opts,
+                schedule,
+                mask.as_ref(),
+                &flat_grid,
+                bitboards,
+                width,
+            )
+        } else if let Some(mask) = &mask {
+            run_masked(&opts, mask, &flat_grid, bitboards, width)
+        } else {
+            run_benchmarks(&opts, &initial_grid, &flat_grid, bitboards, width)
+        };
+        (final_state, rule_name)
+    };
+
+    if opts.apgcode {
+        let rule = rule_name
+            .as_deref()
+            .and_then(|r| r.parse::<Rule>().ok())
+            .unwrap_or_else(|| {
+                eprintln!("--apgcode needs a single Life-like rule");
+                std::process::exit(1);
+            });
+        let pattern = Pattern::from_cells(final_state.clone());
+        match encode_apgcode(&pattern, rule) {
+            Ok(code) => report!("apgcode: {}", code),
+            Err(e) => eprintln!("No apgcode: {}", e),
+        }
+    }
+
+    if let Some(path) = &opts.image_path {
+        let cells = age_map.as_ref().unwrap_or(&final_state);
+        let multi_state = cells.iter().flatten().any(|&c| c > 1);
+        let fallback = match (&age_map, multi_state) {
+            (Some(_), _) => ImageFormat::Pgm,
+            (None, true) => ImageFormat::Ppm,
+            (None, false) => ImageFormat::Pbm,
+        };
+        let format = ImageFormat::from_extension(path).unwrap_or(fallback);
+        write_cells_image(
+            path,
+            cells,
+            format,
+            opts.cell_size.unwrap_or(1).max(1),
+            &opts.palette,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        });
+    }
+
+    if let Some(path) = &opts.png_path {
+        let options = PngOptions {
+            region: opts.region,
+            cell_size: opts.cell_size.unwrap_or(1),
+            grid_lines: opts.grid_lines,
+            palette: opts.palette.clone(),
+        };
+        write_png(path, &final_state, &options).unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        });
+    }
+
+    if let Some(path) = &opts.svg_path {
+        // SVG cells default to 10 units across, so labels stay legible.
+        let defaults = SvgOptions::default();
+        let options = SvgOptions {
+            region: opts.region,
+            cell_size: opts.cell_size.unwrap_or(defaults.cell_size),
+            grid_lines: opts.grid_lines,
+            labels: opts.labels || opts.label_every.is_some(),
+            label_every: opts.label_every.unwrap_or(defaults.label_every),
+            highlight_objects: opts.highlight_objects,
+            palette: opts.palette.clone(),
+        };
+        or_exit_writing(path, write_svg(path, &final_state, &options));
+    }
+
+    if let Some(path) = &opts.output_path {
+        let format = opts
+            .output_format
+            .or_else(|| PatternFormat::from_extension(path))
+            .unwrap_or(PatternFormat::Text);
+        let pattern = Pattern {
+            rule: rule_name,
+            ..Pattern::from_cells(final_state)
+        };
+        let result = if path == "-" {
+            io::stdout()
+                .lock()
+                .write_all(format_pattern(&pattern, format).as_bytes())
+        } else {
+            write_pattern(path, &pattern, format)
+        };
+        result.unwrap_or_else(|e| {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        });
+    }
+}
@@ -0,0 +1,28 @@
+//! Age maps: how long each cell has been alive, drawn like a multi-state
+//! pattern so that still lifes stand out from the churn around them.
+
+/// Consecutive generations each cell of a flat grid has been alive for,
+/// saturating at 255; dead cells are 0.
+pub struct AgeMap
+{
+    pub ages: Vec<u8>,
+}
+
+impl AgeMap
+{
+    pub fn new(area: usize) -> AgeMap
+    {
+        AgeMap {
+            ages: vec![0; area],
+        }
+    }
+
+    /// Counts one more generation: live cells of `cells` age by one and
+    /// dead ones start over.
+    pub fn add_generation(&mut self, cells: &[u8])
+    {
+        for (age, &cell) in self.ages.iter_mut().zip(cells) {
+            *age = if cell != 0 { age.saturating_add(1) } else { 0 };
+        }
+    }
+}
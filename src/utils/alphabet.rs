@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+/// Characters the plain-text loader accepts for each cell state. A space
+/// always reads as dead unless the alphabet says otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet
+{
+    cells: Vec<(char, u8)>,
+}
+
+impl Alphabet
+{
+    /// This project's own `X`/`.` grid, with the digits `1`-`9` naming
+    /// the state of a live cell explicitly.
+    pub fn standard() -> Alphabet
+    {
+        let mut alphabet: Alphabet = ".X".parse().unwrap();
+        for (state, ch) in ('1'..='9').enumerate() {
+            alphabet.cells.push((ch, state as u8 + 1));
+        }
+        alphabet
+    }
+
+    /// Alphabets tried, in order, when none is given.
+    pub fn common() -> Vec<Alphabet>
+    {
+        let mut common = vec![Alphabet::standard()];
+        for spec in [".O", ".*", ".#", "01", "0123456789"] {
+            common.push(spec.parse().unwrap());
+        }
+        common.push(".ABCDEFGHIJKLMNOPQRSTUVWX".parse().unwrap());
+        common
+    }
+
+    /// Picks the first common alphabet that covers every character of
+    /// `rows`. If none does, the first one that at least knows the first
+    /// character is returned so the loader can point at the culprit.
+    pub fn detect(rows: &[String]) -> Alphabet
+    {
+        let common = Alphabet::common();
+        let chars = || rows.iter().flat_map(|r| r.chars());
+        if let Some(found) = common
+            .iter()
+            .find(|a| chars().all(|ch| a.state(ch).is_some()))
+        {
+            return found.clone();
+        }
+        let first = chars().find(|&ch| ch != ' ');
+        common
+            .iter()
+            .find(|a| first.is_some_and(|ch| a.state(ch).is_some()))
+            .unwrap_or(&common[0])
+            .clone()
+    }
+
+    pub fn state(&self, ch: char) -> Option<u8>
+    {
+        match self.cells.iter().find(|&&(c, _)| c == ch) {
+            Some(&(_, state)) => Some(state),
+            None if ch == ' ' => Some(0),
+            None => None,
+        }
+    }
+}
+
+/// Parses `<dead><state 1><state 2>...`, e.g. `.O` or `0123`.
+impl FromStr for Alphabet
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() < 2 {
+            return Err(
+                "need a dead character and at least one live one".to_string()
+            );
+        }
+        if chars.len() > 256 {
+            return Err("at most 255 live states".to_string());
+        }
+        for (i, ch) in chars.iter().enumerate() {
+            if chars[..i].contains(ch) {
+                return Err(format!("'{}' appears twice", ch));
+            }
+        }
+        Ok(Alphabet {
+            cells: chars
+                .into_iter()
+                .enumerate()
+                .map(|(state, ch)| (ch, state as u8))
+                .collect(),
+        })
+    }
+}
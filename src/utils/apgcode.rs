@@ -0,0 +1,309 @@
+//! apgcodes as used by apgsearch and Catagolue: `xs<pop>_` for still lifes,
+//! `xp<period>_` for oscillators and `xq<period>_` for spaceships, followed
+//! by the object in extended Wechsler format. The pattern is cut into
+//! strips five rows tall, each column of a strip is one base-32 digit (top
+//! row in the low bit), `z` separates strips and `w`, `x` and `y?` abbreviate
+//! runs of 2, 3 and 4-39 empty columns.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::utils::{pattern::Pattern, rule::Rule};
+
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Longest period searched for when classifying an object.
+pub const MAX_PERIOD: usize = 1000;
+
+/// True if `code` has an `xs`, `xp` or `xq` prefix.
+pub fn is_apgcode(code: &str) -> bool
+{
+    code.split_once('_').is_some_and(|(prefix, _)| {
+        ["xs", "xp", "xq"].iter().any(|p| {
+            prefix.strip_prefix(p).is_some_and(|n| {
+                !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())
+            })
+        })
+    })
+}
+
+pub fn decode_apgcode(code: &str) -> Result<Pattern, String>
+{
+    if !is_apgcode(code) {
+        return Err(format!("'{}' is not an xs, xp or xq apgcode", code));
+    }
+    let (_, body) = code.split_once('_').unwrap();
+
+    let mut coords = Vec::new();
+    for (strip, part) in body.split('z').enumerate() {
+        let mut x = 0i64;
+        let mut chars = part.chars();
+        while let Some(ch) = chars.next() {
+            let column = match ch {
+                'w' => {
+                    x += 2;
+                    continue;
+                }
+                'x' => {
+                    x += 3;
+                    continue;
+                }
+                'y' => {
+                    let run = chars
+                        .next()
+                        .and_then(|c| digit_value(c).filter(|&v| v < 36))
+                        .ok_or_else(|| format!("bad 'y' run in '{}'", code))?;
+                    x += 4 + run as i64;
+                    continue;
+                }
+                c => digit_value(c).filter(|&v| v < 32).ok_or_else(|| {
+                    format!("unexpected '{}' in '{}'", c, code)
+                })?,
+            };
+            for bit in 0..5 {
+                if (column >> bit) & 1 == 1 {
+                    coords.push((x, strip as i64 * 5 + bit));
+                }
+            }
+            x += 1;
+        }
+    }
+    Pattern::from_coordinates(&coords)
+}
+
+fn digit_value(c: char) -> Option<u32>
+{
+    DIGITS
+        .iter()
+        .position(|&d| d as char == c)
+        .map(|v| v as u32)
+}
+
+/// Canonical apgcode of a still life, oscillator or spaceship under `rule`.
+/// Every phase is tried in all eight orientations and the shortest
+/// encoding wins, ties going to the lexicographically smallest.
+pub fn encode_apgcode(pattern: &Pattern, rule: Rule) -> Result<String, String>
+{
+    if rule.born(0) {
+        return Err("apgcodes are not defined for B0 rules".to_string());
+    }
+    let start: HashSet<(i64, i64)> =
+        pattern.coordinates().into_iter().collect();
+    if start.is_empty() {
+        return Ok("xs0_0".to_string());
+    }
+
+    let mut phases = vec![start.clone()];
+    let mut cells = start.clone();
+    for period in 1..=MAX_PERIOD {
+        cells = step(&cells, rule);
+        if cells.is_empty() {
+            return Err("pattern dies out".to_string());
+        }
+        let (dx, dy) = offset(&cells, &start);
+        let moved: HashSet<_> =
+            cells.iter().map(|&(x, y)| (x - dx, y - dy)).collect();
+        if moved == start {
+            let prefix = match (period, dx == 0 && dy == 0) {
+                (1, true) => format!("xs{}", start.len()),
+                (_, true) => format!("xp{}", period),
+                (_, false) => format!("xq{}", period),
+            };
+            let body = phases
+                .iter()
+                .map(canonical_wechsler)
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+                .unwrap();
+            return Ok(format!("{}_{}", prefix, body));
+        }
+        phases.push(cells.clone());
+    }
+    Err(format!(
+        "not a still life, oscillator or spaceship within {} generations",
+        MAX_PERIOD
+    ))
+}
+
+/// One generation of a sparse set of live cells; B0 rules are not
+/// supported.
+pub(crate) fn step(
+    cells: &HashSet<(i64, i64)>,
+    rule: Rule,
+) -> HashSet<(i64, i64)>
+{
+    let mut counts: HashMap<(i64, i64), u8> = HashMap::new();
+    for &(x, y) in cells {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx != 0 || dy != 0 {
+                    *counts.entry((x + dx, y + dy)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    // Cells with no live neighbour stay dead since B0 is rejected, but a
+    // live one may still survive on S0.
+    for &cell in cells {
+        counts.entry(cell).or_insert(0);
+    }
+    counts
+        .into_iter()
+        .filter(|&(cell, count)| {
+            rule.next(cells.contains(&cell) as u8, count) != 0
+        })
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+/// Translation taking the top-left corner of `b`'s bounding box to `a`'s.
+fn offset(a: &HashSet<(i64, i64)>, b: &HashSet<(i64, i64)>) -> (i64, i64)
+{
+    let corner = |s: &HashSet<(i64, i64)>| {
+        (
+            s.iter().map(|c| c.0).min().unwrap(),
+            s.iter().map(|c| c.1).min().unwrap(),
+        )
+    };
+    let (ax, ay) = corner(a);
+    let (bx, by) = corner(b);
+    (ax - bx, ay - by)
+}
+
+fn canonical_wechsler(cells: &HashSet<(i64, i64)>) -> Result<String, String>
+{
+    const ORIENTATIONS: [(i64, i64, i64, i64); 8] = [
+        (1, 0, 0, 1),
+        (-1, 0, 0, 1),
+        (1, 0, 0, -1),
+        (-1, 0, 0, -1),
+        (0, 1, 1, 0),
+        (0, -1, 1, 0),
+        (0, 1, -1, 0),
+        (0, -1, -1, 0),
+    ];
+    ORIENTATIONS
+        .iter()
+        .map(|&(a, b, c, d)| {
+            let coords: Vec<_> = cells
+                .iter()
+                .map(|&(x, y)| (a * x + b * y, c * x + d * y))
+                .collect();
+            Ok(wechsler(&Pattern::from_coordinates(&coords)?))
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map(|codes| {
+            codes
+                .into_iter()
+                .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+                .unwrap()
+        })
+}
+
+fn wechsler(pattern: &Pattern) -> String
+{
+    let mut out = String::new();
+    for strip in 0..pattern.height.div_ceil(5) {
+        if strip > 0 {
+            out.push('z');
+        }
+        let mut zeroes = 0usize;
+        for x in 0..pattern.width {
+            let column = (0..5)
+                .filter(|&bit| {
+                    pattern
+                        .cells
+                        .get(strip * 5 + bit)
+                        .is_some_and(|row| row[x] != 0)
+                })
+                .fold(0usize, |acc, bit| acc | 1 << bit);
+            if column == 0 {
+                zeroes += 1;
+                continue;
+            }
+            while zeroes > 0 {
+                let run = zeroes.min(39);
+                match run {
+                    1 => out.push('0'),
+                    2 => out.push('w'),
+                    3 => out.push('x'),
+                    n => {
+                        out.push('y');
+                        out.push(DIGITS[n - 4] as char);
+                    }
+                }
+                zeroes -= run;
+            }
+            out.push(DIGITS[column] as char);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn block()
+    {
+        let block = decode_apgcode("xs4_33").unwrap();
+        assert_eq!(block.cells, vec![vec![1, 1], vec![1, 1]]);
+        assert_eq!(encode_apgcode(&block, Rule::conway()).unwrap(), "xs4_33");
+    }
+
+    #[test]
+    fn glider()
+    {
+        let glider = decode_apgcode("xq4_153").unwrap();
+        assert_eq!(
+            glider.cells,
+            vec![vec![1, 1, 1], vec![0, 0, 1], vec![0, 1, 0]]
+        );
+        // Any phase and orientation encodes to the same code.
+        let other = Pattern::from_cells(vec![
+            vec![0, 1, 0],
+            vec![0, 0, 1],
+            vec![1, 1, 1],
+        ]);
+        for pattern in [glider, other] {
+            assert_eq!(
+                encode_apgcode(&pattern, Rule::conway()).unwrap(),
+                "xq4_153"
+            );
+        }
+    }
+
+    #[test]
+    fn blinker()
+    {
+        let blinker = Pattern::from_cells(vec![vec![1, 1, 1]]);
+        assert_eq!(encode_apgcode(&blinker, Rule::conway()).unwrap(), "xp2_7");
+    }
+
+    #[test]
+    fn gaps_and_strips()
+    {
+        let pattern = decode_apgcode("xs2_1y01z1").unwrap();
+        assert_eq!((pattern.width, pattern.height), (6, 6));
+        assert_eq!(pattern.coordinates(), vec![(0, 0), (5, 0), (0, 5)]);
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected()
+    {
+        for code in ["xs4_3!", "xs4_y", "xs4_y!", "xs_33", "ov_s23", "33"] {
+            assert!(decode_apgcode(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn unencodable_patterns_are_rejected()
+    {
+        let cell = Pattern::from_cells(vec![vec![1]]);
+        assert!(encode_apgcode(&cell, Rule::conway()).is_err());
+        let b0 = "B0/S23".parse().unwrap();
+        assert!(encode_apgcode(&cell, b0).is_err());
+    }
+}
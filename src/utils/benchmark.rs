@@ -46,7 +46,10 @@ where
     (final_state, start.elapsed())
 } */
 
-pub fn benchmark<Input, Output, F>(input: &Input, mut simulate: F) -> (Output, Duration)
+pub fn benchmark<Input, Output, F>(
+    input: &Input,
+    mut simulate: F,
+) -> (Output, Duration)
 where
     Input: Clone,
     F: FnMut(Input) -> Output,
@@ -56,4 +59,3 @@ where
     let elapsed = start.elapsed();
     (result, elapsed)
 }
-
@@ -0,0 +1,287 @@
+//! Binary snapshots of a bitboard run, so that a long run can stop and
+//! later resume with exactly the same results.
+//!
+//! All integers are little-endian:
+//!
+//! | bytes | field                                              |
+//! |-------|----------------------------------------------------|
+//! | 8     | magic `LIFECKPT`                                   |
+//! | 2     | format version (1)                                 |
+//! | 1     | background state, which B0 rules flip              |
+//! | 1     | length `n` of the rule                             |
+//! | n     | rule in B/S notation, used for the next generation |
+//! | 4     | width in cells, a multiple of 64                   |
+//! | 4     | height in cells                                    |
+//! | 8     | generation number                                  |
+//! | 8 + 8 | origin: coordinates of the top-left cell           |
+//! | 4     | CRC-32 of the whole file with this field zeroed    |
+//!
+//! The payload follows: each row as `width / 64` words of 64 cells, the
+//! leftmost cell in the lowest bit, exactly as the bitboard engines hold it.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::utils::{
+    error::{LoadError, MAX_SIDE},
+    png::crc32,
+    rule::Rule,
+};
+
+const MAGIC: &[u8; 8] = b"LIFECKPT";
+const VERSION: u16 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint
+{
+    pub rule: Rule,
+    /// Width in cells; each row of `board` holds `width / 64` words.
+    pub width: usize,
+    pub generation: usize,
+    pub origin: (i64, i64),
+    pub background: u8,
+    pub board: Vec<Vec<u64>>,
+}
+
+impl Checkpoint
+{
+    pub fn height(&self) -> usize
+    {
+        self.board.len()
+    }
+}
+
+/// True if the file at `path` starts with the checkpoint magic number.
+pub fn is_checkpoint_file(path: &str) -> bool
+{
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == MAGIC)
+}
+
+pub fn encode_checkpoint(checkpoint: &Checkpoint) -> Vec<u8>
+{
+    let rule = checkpoint.rule.to_string();
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.push(checkpoint.background);
+    out.push(rule.len() as u8);
+    out.extend_from_slice(rule.as_bytes());
+    out.extend_from_slice(&(checkpoint.width as u32).to_le_bytes());
+    out.extend_from_slice(&(checkpoint.height() as u32).to_le_bytes());
+    out.extend_from_slice(&(checkpoint.generation as u64).to_le_bytes());
+    out.extend_from_slice(&checkpoint.origin.0.to_le_bytes());
+    out.extend_from_slice(&checkpoint.origin.1.to_le_bytes());
+    let checksum_at = out.len();
+    out.extend_from_slice(&[0; 4]);
+    for word in checkpoint.board.iter().flatten() {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+
+    let checksum = crc32(&out);
+    out[checksum_at..checksum_at + 4].copy_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// Splits `n` bytes off the front of `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], String>
+{
+    if bytes.len() < n {
+        return Err("truncated checkpoint".to_string());
+    }
+    let (head, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], String>
+{
+    Ok(take(bytes, N)?.try_into().unwrap())
+}
+
+pub fn parse_checkpoint(bytes: &[u8]) -> Result<Checkpoint, String>
+{
+    let mut rest = bytes;
+    if take(&mut rest, 8)? != MAGIC {
+        return Err("not a checkpoint".to_string());
+    }
+    let version = u16::from_le_bytes(take_array(&mut rest)?);
+    if version != VERSION {
+        return Err(format!("unsupported checkpoint version {}", version));
+    }
+    let [background, rule_len] = take_array(&mut rest)?;
+    let rule = std::str::from_utf8(take(&mut rest, rule_len as usize)?)
+        .map_err(|_| "rule is not valid UTF-8".to_string())?
+        .parse::<Rule>()?;
+    let width = u32::from_le_bytes(take_array(&mut rest)?) as usize;
+    let height = u32::from_le_bytes(take_array(&mut rest)?) as usize;
+    let generation = u64::from_le_bytes(take_array(&mut rest)?);
+    let origin = (
+        i64::from_le_bytes(take_array(&mut rest)?),
+        i64::from_le_bytes(take_array(&mut rest)?),
+    );
+
+    let checksum_at = bytes.len() - rest.len();
+    let checksum = u32::from_le_bytes(take_array(&mut rest)?);
+    let mut zeroed = bytes.to_vec();
+    zeroed[checksum_at..checksum_at + 4].fill(0);
+    if crc32(&zeroed) != checksum {
+        return Err("checksum mismatch; the file is damaged".to_string());
+    }
+
+    if background > 1 {
+        return Err(format!("invalid background state {}", background));
+    }
+    if height == 0 {
+        return Err("empty grid".to_string());
+    }
+    if width == 0 || !width.is_multiple_of(64) {
+        return Err(format!("width {} is not a multiple of 64", width));
+    }
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(format!("{}x{} grid is too large", width, height));
+    }
+    let words = width / 64;
+    if rest.len() != words * height * 8 {
+        return Err(format!(
+            "payload is {} bytes, expected {}",
+            rest.len(),
+            words * height * 8
+        ));
+    }
+    let board = rest
+        .chunks(words * 8)
+        .map(|row| {
+            row.chunks(8)
+                .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                .collect()
+        })
+        .collect();
+
+    Ok(Checkpoint {
+        rule,
+        width,
+        generation: usize::try_from(generation)
+            .map_err(|_| "generation number out of range".to_string())?,
+        origin,
+        background,
+        board,
+    })
+}
+
+pub fn read_checkpoint(path: &str) -> Result<Checkpoint, LoadError>
+{
+    let bytes = fs::read(path).map_err(|e| LoadError::io(path, e))?;
+    parse_checkpoint(&bytes).map_err(|e| LoadError::invalid(path, e))
+}
+
+/// Writes through a temporary file and renames it into place, so an
+/// interrupted write never leaves a damaged checkpoint behind.
+pub fn write_checkpoint(
+    path: impl AsRef<Path>,
+    checkpoint: &Checkpoint,
+) -> io::Result<()>
+{
+    let path = path.as_ref();
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, encode_checkpoint(checkpoint))?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{gol_five::gol_five_resumed, utils::schedule::Schedule};
+
+    /// Random 128x40 board from a fixed xorshift seed.
+    fn board() -> Vec<Vec<u64>>
+    {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..40)
+            .map(|_| {
+                (0..2)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        state
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn checkpoint() -> Checkpoint
+    {
+        Checkpoint {
+            rule: Rule::conway(),
+            width: 128,
+            generation: 7,
+            origin: (-3, 5),
+            background: 0,
+            board: board(),
+        }
+    }
+
+    #[test]
+    fn round_trip()
+    {
+        let checkpoint = checkpoint();
+        let bytes = encode_checkpoint(&checkpoint);
+        assert_eq!(parse_checkpoint(&bytes), Ok(checkpoint));
+    }
+
+    #[test]
+    fn corrupted_checksum_is_rejected()
+    {
+        let bytes = encode_checkpoint(&checkpoint());
+
+        let mut damaged = bytes.clone();
+        *damaged.last_mut().unwrap() ^= 1;
+        assert!(parse_checkpoint(&damaged).is_err());
+
+        // The checksum sits just before the payload.
+        let checksum_at = bytes.len() - 40 * 2 * 8 - 4;
+        let mut damaged = bytes;
+        damaged[checksum_at] ^= 1;
+        assert!(parse_checkpoint(&damaged).is_err());
+    }
+
+    #[test]
+    fn resumed_run_matches_an_uninterrupted_run()
+    {
+        // The B0 rule flips the background, which the checkpoint carries.
+        let schedule = Schedule::new(
+            vec![(Rule::conway(), 5), ("B036/S23".parse().unwrap(), 3)],
+            true,
+        );
+        let whole = gol_five_resumed(board(), 128, &schedule, None, (0, 0), 30);
+
+        let (board, background) =
+            gol_five_resumed(board(), 128, &schedule, None, (0, 0), 13);
+        let saved = encode_checkpoint(&Checkpoint {
+            rule: schedule.rule_at(13),
+            width: 128,
+            generation: 13,
+            origin: (0, 0),
+            background,
+            board,
+        });
+        let start = parse_checkpoint(&saved).unwrap();
+        let resumed = gol_five_resumed(
+            start.board,
+            start.width,
+            &schedule,
+            None,
+            (start.generation, start.background),
+            17,
+        );
+        assert_eq!(resumed, whole);
+    }
+}
@@ -1,7 +1,7 @@
 pub fn flatten_grid(grid: &[Vec<bool>]) -> Vec<u8>
 {
     let h = grid.len();
-    let w = grid.get(0).map_or(0, |r| r.len());
+    let w = grid.first().map_or(0, |r| r.len());
     let mut flat = Vec::with_capacity(h * w);
     for row in grid {
         flat.extend(row.iter().map(|&b| b as u8));
@@ -9,11 +9,19 @@ pub fn flatten_grid(grid: &[Vec<bool>]) -> Vec<u8>
     flat
 }
 
+pub fn flatten_colour_grid(grid: &[Vec<u8>]) -> Vec<u8>
+{
+    grid.concat()
+}
+
 pub fn to_bitboards(grid: &[Vec<bool>]) -> (Vec<Vec<u64>>, usize)
 {
     let height = grid.len();
     let width = grid.first().map(|r| r.len()).unwrap_or(0);
-    assert!(width % 64 == 0, "bitboard width must be a multiple of 64");
+    assert!(
+        width.is_multiple_of(64),
+        "bitboard width must be a multiple of 64"
+    );
     let words_per_row = width / 64;
 
     let mut board = Vec::with_capacity(height);
@@ -31,3 +39,20 @@ pub fn to_bitboards(grid: &[Vec<bool>]) -> (Vec<Vec<u64>>, usize)
     }
     (board, width)
 }
+
+pub fn unflatten_grid(flat: &[u8], width: usize) -> Vec<Vec<u8>>
+{
+    flat.chunks(width).map(|row| row.to_vec()).collect()
+}
+
+pub fn from_bitboards(board: &[Vec<u64>], width: usize) -> Vec<Vec<u8>>
+{
+    board
+        .iter()
+        .map(|words| {
+            (0..width)
+                .map(|col| ((words[col / 64] >> (col % 64)) & 1) as u8)
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,146 @@
+use std::{error::Error, fmt, io};
+
+/// Largest width or height accepted from an input file; grids are padded to
+/// a square, so this already means a billion cells.
+pub const MAX_SIDE: usize = 1 << 15;
+
+/// Everything that can go wrong turning command-line arguments and input
+/// files into something the engines can run.
+#[derive(Debug)]
+pub enum LoadError
+{
+    Io
+    {
+        path: String, source: io::Error
+    },
+    UnexpectedChar
+    {
+        path: String,
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    Empty
+    {
+        path: String
+    },
+    /// A grid row whose width differs from the first row's.
+    Ragged
+    {
+        path: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    Oversized
+    {
+        path: String,
+        width: usize,
+        height: usize,
+    },
+    UnknownFormat
+    {
+        name: String
+    },
+    /// The file was read but its contents did not parse.
+    Invalid
+    {
+        path: String, message: String
+    },
+    MissingArgument
+    {
+        name: String
+    },
+    InvalidArgument
+    {
+        name: String,
+        value: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for LoadError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            LoadError::Io { path, source } => {
+                write!(f, "cannot read {}: {}", path, source)
+            }
+            LoadError::UnexpectedChar {
+                path,
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "{}:{}:{}: unexpected character '{}'",
+                path, line, column, found
+            ),
+            LoadError::Empty { path } => write!(f, "{} has no cells", path),
+            LoadError::Ragged {
+                path,
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: row is {} cells wide, expected {}",
+                path, line, found, expected
+            ),
+            LoadError::Oversized {
+                path,
+                width,
+                height,
+            } => write!(
+                f,
+                "{} is {}x{}, larger than the {}x{} limit",
+                path, width, height, MAX_SIDE, MAX_SIDE
+            ),
+            LoadError::UnknownFormat { name } => {
+                write!(f, "unknown format '{}'", name)
+            }
+            LoadError::Invalid { path, message } => {
+                write!(f, "{}: {}", path, message)
+            }
+            LoadError::MissingArgument { name } => {
+                write!(f, "missing argument '{}'", name)
+            }
+            LoadError::InvalidArgument {
+                name,
+                value,
+                message,
+            } => write!(f, "invalid {} '{}': {}", name, value, message),
+        }
+    }
+}
+
+impl Error for LoadError
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)>
+    {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl LoadError
+{
+    pub fn io(path: &str, source: io::Error) -> LoadError
+    {
+        LoadError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn invalid(path: &str, message: impl fmt::Display) -> LoadError
+    {
+        LoadError::Invalid {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Where the flat engines get each cell's rule from, so that one stepping
+//! loop in gol_two and gol_three serves plain runs, schedules, rule maps
+//! and weighted neighbourhoods alike.
+
+use crate::utils::{
+    neighbourhood::{CompiledNeighbourhood, WeightedNeighbourhood},
+    rule::RuleMap,
+    schedule::Schedule,
+};
+
+/// Sum of a cell's neighbourhood in a padded flat buffer, less the
+/// smallest possible sum, so that it indexes the next-state tables.
+pub trait Neighbours: Sync
+{
+    /// # Safety
+    ///
+    /// `idx` must be at least the margin away from the edges of the buffer
+    /// that `ptr` points into.
+    unsafe fn sum(&self, ptr: *const u8, idx: isize) -> usize;
+}
+
+/// The eight Moore neighbours, counted without weights; kept apart from
+/// CompiledNeighbourhood so the common case compiles to fixed loads.
+pub struct Moore
+{
+    offsets: [isize; 8],
+}
+
+impl Moore
+{
+    pub fn new(padded_width: usize) -> Moore
+    {
+        let pw = padded_width as isize;
+        Moore {
+            offsets: [-(pw + 1), -pw, -(pw - 1), -1, 1, pw - 1, pw, pw + 1],
+        }
+    }
+}
+
+impl Neighbours for Moore
+{
+    #[inline(always)]
+    unsafe fn sum(&self, ptr: *const u8, idx: isize) -> usize
+    {
+        let mut cnt: u8 = 0;
+        for &off in &self.offsets {
+            cnt += *ptr.offset(idx + off);
+        }
+        cnt as usize
+    }
+}
+
+impl Neighbours for CompiledNeighbourhood
+{
+    #[inline(always)]
+    unsafe fn sum(&self, ptr: *const u8, idx: isize) -> usize
+    {
+        let mut sum = -self.min_sum;
+        for (&off, &w) in self.offsets.iter().zip(&self.weights) {
+            sum += w * *ptr.offset(idx + off) as i32;
+        }
+        sum as usize
+    }
+}
+
+/// The rule every cell follows in every generation.
+#[derive(Clone, Copy)]
+pub enum FlatRules<'a>
+{
+    /// All cells follow `schedule.rule_at(g)` to compute generation
+    /// `g + 1`.
+    Schedule(&'a Schedule),
+    /// Each cell follows the rule of its region in every generation;
+    /// cells beyond the edge follow the first rule of the map.
+    Map(&'a RuleMap),
+    /// All cells count a weighted neighbourhood instead of the Moore one.
+    Weighted(&'a WeightedNeighbourhood),
+}
+
+impl FlatRules<'_>
+{
+    /// Width of the padding the neighbourhood reaches into.
+    pub fn margin(&self) -> usize
+    {
+        match self {
+            FlatRules::Weighted(neighbourhood) => neighbourhood.radius(),
+            _ => 1,
+        }
+    }
+
+    /// The neighbourhood, compiled for a buffer `padded_width` wide.
+    pub fn kernel(&self, padded_width: usize) -> CompiledNeighbourhood
+    {
+        match self {
+            FlatRules::Weighted(neighbourhood) => {
+                neighbourhood.compile(padded_width)
+            }
+            _ => WeightedNeighbourhood::moore().compile(padded_width),
+        }
+    }
+
+    /// Next-state tables for generation `generation`, one per region and
+    /// each laid out like `kernel.table`.
+    pub fn tables(
+        &self,
+        kernel: &CompiledNeighbourhood,
+        generation: usize,
+    ) -> Vec<u8>
+    {
+        match self {
+            FlatRules::Schedule(schedule) => {
+                schedule.rule_at(generation).table().to_vec()
+            }
+            FlatRules::Map(map) => map.tables().concat(),
+            FlatRules::Weighted(_) => kernel.table.clone(),
+        }
+    }
+
+    /// Region of every cell in the flat layout, if there is more than one.
+    pub fn regions(&self) -> Option<&[u8]>
+    {
+        match self {
+            FlatRules::Map(map) => Some(&map.regions),
+            _ => None,
+        }
+    }
+}
+
+/// State beyond the edge of the grid after a generation computed with
+/// `tables`, given the state before it; the edge follows the first
+/// region's rule. A uniform background sums to the kernel's total
+/// weight when live, so rules that give birth or death on that sum make
+/// it strobe.
+pub fn background_next(
+    kernel: &CompiledNeighbourhood,
+    tables: &[u8],
+    background: u8,
+) -> u8
+{
+    let total: i32 = kernel.weights.iter().sum();
+    let sum = total * background as i32 - kernel.min_sum;
+    tables[background as usize * kernel.span + sum as usize]
+}
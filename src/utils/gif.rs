@@ -0,0 +1,217 @@
+//! Streaming animated GIF writer. Frames are encoded as they arrive, so a
+//! run can be recorded without holding its history. The global colour
+//! table has one entry per cell state, taken from the palette.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::utils::{
+    palette::Palette,
+    png::{rasterise, BitWriter, Region},
+};
+
+/// Generations `first..=last` to record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameRange
+{
+    pub first: usize,
+    pub last: usize,
+}
+
+/// Parses `first..last`, either end optional.
+impl FromStr for FrameRange
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let (first, last) = s
+            .split_once("..")
+            .ok_or_else(|| "expected 'first..last'".to_string())?;
+        let bound = |b: &str, default| match b.trim() {
+            "" => Ok(default),
+            b => b.parse::<usize>().map_err(|e| e.to_string()),
+        };
+        let range = FrameRange {
+            first: bound(first, 0)?,
+            last: bound(last, usize::MAX)?,
+        };
+        if range.first > range.last {
+            return Err("first frame is after the last".to_string());
+        }
+        Ok(range)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GifOptions
+{
+    /// Part of the grid to draw; the whole grid when `None`.
+    pub region: Option<Region>,
+    /// Side of a cell in pixels.
+    pub cell_size: usize,
+    /// Time each frame is shown, in hundredths of a second.
+    pub delay: u16,
+    pub palette: Palette,
+}
+
+impl Default for GifOptions
+{
+    fn default() -> Self
+    {
+        GifOptions {
+            region: None,
+            cell_size: 1,
+            delay: 10,
+            palette: Palette::default(),
+        }
+    }
+}
+
+pub struct GifWriter<W: Write>
+{
+    out: W,
+    region: Region,
+    cell_size: usize,
+    delay: u16,
+}
+
+impl<W: Write> GifWriter<W>
+{
+    /// Writes the header for frames of a `grid_width`x`grid_height` grid;
+    /// the animation loops forever.
+    pub fn new(
+        mut out: W,
+        grid_width: usize,
+        grid_height: usize,
+        options: &GifOptions,
+    ) -> io::Result<GifWriter<W>>
+    {
+        let region = options
+            .region
+            .unwrap_or(Region {
+                x: 0,
+                y: 0,
+                width: grid_width,
+                height: grid_height,
+            })
+            .clamp(grid_width, grid_height)?;
+        let cell_size = options.cell_size.max(1);
+        let too_big = |cells: usize| cells * cell_size > u16::MAX as usize;
+        if too_big(region.width) || too_big(region.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIF frames are limited to 65535 pixels a side",
+            ));
+        }
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&((region.width * cell_size) as u16).to_le_bytes())?;
+        out.write_all(&((region.height * cell_size) as u16).to_le_bytes())?;
+        // Global 256-entry colour table, 8 bits per primary.
+        out.write_all(&[0xF7, 0, 0])?;
+        for state in 0..=255u8 {
+            out.write_all(&options.palette.colour(state))?;
+        }
+        // NETSCAPE2.0 extension: loop forever.
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(GifWriter {
+            out,
+            region,
+            cell_size,
+            delay: options.delay,
+        })
+    }
+
+    /// Appends the flat grid `cells`, laid out `grid_width` cells wide.
+    pub fn add_frame(
+        &mut self,
+        cells: &[u8],
+        grid_width: usize,
+    ) -> io::Result<()>
+    {
+        let Region { width, height, .. } = self.region;
+        let scale = self.cell_size;
+        let pixels = rasterise(cells, grid_width, self.region, scale);
+
+        // Graphic control extension with the frame delay.
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&self.delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole canvas.
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out
+            .write_all(&((width * scale) as u16).to_le_bytes())?;
+        self.out
+            .write_all(&((height * scale) as u16).to_le_bytes())?;
+        self.out.write_all(&[0x00, 8])?;
+
+        for block in lzw(&pixels).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    /// Writes the trailer and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W>
+    {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+const CLEAR: u16 = 256;
+const END: u16 = 257;
+const MAX_CODES: u16 = 4096;
+
+/// GIF-flavoured LZW with 8-bit pixels: codes start at 9 bits and grow to
+/// 12, after which the table is cleared.
+fn lzw(pixels: &[u8]) -> Vec<u8>
+{
+    let mut w = BitWriter::new(Vec::new());
+    let mut size = 9u32;
+    let mut next = END + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    w.put(CLEAR as u32, size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        w.put(END as u32, size);
+        return w.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        w.put(prefix as u32, size);
+        if next < MAX_CODES {
+            // The decoder adds each entry one code later, so it widens its
+            // codes when `next` reaches 1 << size rather than passes it.
+            if next == 1 << size && size < 12 {
+                size += 1;
+            }
+            table.insert((prefix, pixel), next);
+            next += 1;
+        } else {
+            w.put(CLEAR as u32, size);
+            table.clear();
+            next = END + 1;
+            size = 9;
+        }
+        prefix = pixel as u16;
+    }
+    w.put(prefix as u32, size);
+    if next == 1 << size && size < 12 {
+        size += 1;
+    }
+    w.put(END as u32, size);
+    w.finish()
+}
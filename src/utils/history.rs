@@ -0,0 +1,389 @@
+//! Whole-run history files: a keyframe every so often and, for the
+//! generations in between, only the cells born and the cells that died.
+//! An index of keyframes at the end gives random access to any generation
+//! by replaying at most one keyframe interval of deltas.
+//!
+//! Layout, integers little-endian:
+//!
+//! - header: magic `LIFEHIST`, version (u16), width and height (u32),
+//!   keyframe interval (u32);
+//! - one record per generation: a keyframe with the cells packed 8 to a
+//!   byte in reading order, lowest bit first, or a delta with the births
+//!   and then the deaths, each a varint count followed by varint gaps
+//!   between increasing cell indices;
+//! - index: the file offset of every keyframe (u64), then the number of
+//!   generations (u64) and the offset of the index itself (u64).
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+use crate::utils::error::{LoadError, MAX_SIDE};
+
+const MAGIC: &[u8; 8] = b"LIFEHIST";
+const VERSION: u16 = 1;
+const HEADER_LEN: u64 = 22;
+
+fn invalid_data(message: &str) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64)
+{
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut impl Read) -> io::Result<u64>
+{
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("varint too long"))
+}
+
+/// Varint count of `indices` followed by the gaps between them.
+fn write_indices(out: &mut Vec<u8>, indices: &[usize])
+{
+    write_varint(out, indices.len() as u64);
+    let mut previous = 0;
+    for &i in indices {
+        write_varint(out, (i - previous) as u64);
+        previous = i;
+    }
+}
+
+/// Records a run one generation at a time.
+pub struct HistoryWriter<W: Write>
+{
+    out: W,
+    area: usize,
+    interval: usize,
+    previous: Vec<u8>,
+    generations: u64,
+    offset: u64,
+    keyframes: Vec<u64>,
+}
+
+impl<W: Write> HistoryWriter<W>
+{
+    /// Writes the header for a `width`x`height` grid with a keyframe every
+    /// `interval` generations.
+    pub fn new(
+        mut out: W,
+        width: usize,
+        height: usize,
+        interval: usize,
+    ) -> io::Result<HistoryWriter<W>>
+    {
+        let interval = interval.max(1);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(width as u32).to_le_bytes())?;
+        out.write_all(&(height as u32).to_le_bytes())?;
+        out.write_all(&(interval as u32).to_le_bytes())?;
+        Ok(HistoryWriter {
+            out,
+            area: width * height,
+            interval,
+            previous: vec![0; width * height],
+            generations: 0,
+            offset: HEADER_LEN,
+            keyframes: Vec::new(),
+        })
+    }
+
+    /// Appends the next generation; any non-zero state counts as live.
+    pub fn add_frame(&mut self, cells: &[u8]) -> io::Result<()>
+    {
+        assert_eq!(cells.len(), self.area, "frame size changed");
+        let mut record = Vec::new();
+        if self.generations.is_multiple_of(self.interval as u64) {
+            self.keyframes.push(self.offset);
+            record.resize(self.area.div_ceil(8), 0);
+            for (i, _) in cells.iter().enumerate().filter(|(_, &c)| c != 0) {
+                record[i / 8] |= 1 << (i % 8);
+            }
+        } else {
+            let changed = |born: bool| {
+                cells
+                    .iter()
+                    .zip(&self.previous)
+                    .enumerate()
+                    .filter(move |(_, (&now, &before))| {
+                        (now != 0) != (before != 0) && (now != 0) == born
+                    })
+                    .map(|(i, _)| i)
+                    .collect::<Vec<usize>>()
+            };
+            write_indices(&mut record, &changed(true));
+            write_indices(&mut record, &changed(false));
+        }
+        self.out.write_all(&record)?;
+        self.offset += record.len() as u64;
+        self.generations += 1;
+        for (p, &c) in self.previous.iter_mut().zip(cells) {
+            *p = (c != 0) as u8;
+        }
+        Ok(())
+    }
+
+    /// Writes the index and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W>
+    {
+        for offset in &self.keyframes {
+            self.out.write_all(&offset.to_le_bytes())?;
+        }
+        self.out.write_all(&self.generations.to_le_bytes())?;
+        self.out.write_all(&self.offset.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Random access to the generations of a history file.
+pub struct HistoryReader<R: Read + Seek>
+{
+    input: BufReader<R>,
+    pub width: usize,
+    pub height: usize,
+    interval: usize,
+    generations: usize,
+    keyframes: Vec<u64>,
+}
+
+impl HistoryReader<File>
+{
+    pub fn open(path: &str) -> Result<HistoryReader<File>, LoadError>
+    {
+        let file = File::open(path).map_err(|e| LoadError::io(path, e))?;
+        HistoryReader::new(file).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                LoadError::invalid(path, e.to_string())
+            }
+            _ => LoadError::io(path, e),
+        })
+    }
+}
+
+impl<R: Read + Seek> HistoryReader<R>
+{
+    pub fn new(input: R) -> io::Result<HistoryReader<R>>
+    {
+        let mut input = BufReader::new(input);
+        let mut header = [0u8; HEADER_LEN as usize];
+        input.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(invalid_data("not a history file"));
+        }
+        let field = |at: usize| {
+            u32::from_le_bytes(header[at..at + 4].try_into().unwrap()) as usize
+        };
+        let version = u16::from_le_bytes([header[8], header[9]]);
+        if version != VERSION {
+            return Err(invalid_data("unsupported history version"));
+        }
+        let (width, height, interval) = (field(10), field(14), field(18));
+        if interval == 0 {
+            return Err(invalid_data("keyframe interval is zero"));
+        }
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(invalid_data(&format!(
+                "{}x{} grid is larger than the {}x{} limit",
+                width, height, MAX_SIDE, MAX_SIDE
+            )));
+        }
+
+        let len = input.seek(SeekFrom::End(0))?;
+        if len < HEADER_LEN + 16 {
+            return Err(invalid_data("truncated history"));
+        }
+        let end = input.seek(SeekFrom::End(-16))?;
+        let mut footer = [0u8; 16];
+        input.read_exact(&mut footer)?;
+        let generations = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let index = u64::from_le_bytes(footer[8..].try_into().unwrap());
+        let count = generations.div_ceil(interval as u64);
+        if count.checked_mul(8).and_then(|n| index.checked_add(n)) != Some(end)
+        {
+            return Err(invalid_data("damaged index"));
+        }
+        input.seek(SeekFrom::Start(index))?;
+        let keyframes = (0..count)
+            .map(|_| {
+                let mut offset = [0u8; 8];
+                input.read_exact(&mut offset)?;
+                Ok(u64::from_le_bytes(offset))
+            })
+            .collect::<io::Result<Vec<u64>>>()?;
+        // Every keyframe must lie whole between the header and the index.
+        let keyframe_len = (width * height).div_ceil(8) as u64;
+        let misplaced = |&offset: &u64| {
+            offset < HEADER_LEN
+                || offset.checked_add(keyframe_len).is_none_or(|e| e > index)
+        };
+        if keyframes.iter().any(misplaced) {
+            return Err(invalid_data("keyframe offset out of range"));
+        }
+
+        Ok(HistoryReader {
+            input,
+            width,
+            height,
+            interval,
+            generations: generations as usize,
+            keyframes,
+        })
+    }
+
+    /// Number of generations recorded, counting the initial one.
+    pub fn len(&self) -> usize
+    {
+        self.generations
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.generations == 0
+    }
+
+    /// Cells (0 or 1, flat) of `generation`.
+    pub fn frame(&mut self, generation: usize) -> io::Result<Vec<u8>>
+    {
+        if generation >= self.generations {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "generation {} is past the end of the history ({})",
+                    generation, self.generations
+                ),
+            ));
+        }
+        let area = self.width * self.height;
+        self.input.seek(SeekFrom::Start(
+            self.keyframes[generation / self.interval],
+        ))?;
+        let mut packed = vec![0u8; area.div_ceil(8)];
+        self.input.read_exact(&mut packed)?;
+        let mut cells: Vec<u8> =
+            (0..area).map(|i| (packed[i / 8] >> (i % 8)) & 1).collect();
+
+        for _ in 0..generation % self.interval {
+            for state in [1, 0] {
+                let mut i = 0usize;
+                for _ in 0..read_varint(&mut self.input)? {
+                    i += read_varint(&mut self.input)? as usize;
+                    *cells.get_mut(i).ok_or_else(|| {
+                        invalid_data("cell index out of range")
+                    })? = state;
+                }
+            }
+        }
+        Ok(cells)
+    }
+}
+
+/// True if the file at `path` starts with the history magic number.
+pub fn is_history_file(path: &str) -> bool
+{
+    let mut magic = [0u8; 8];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == MAGIC)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Ten 5x3 generations of pseudo-random cells.
+    fn frames() -> Vec<Vec<u8>>
+    {
+        let mut state = 12345u32;
+        (0..10)
+            .map(|_| {
+                (0..15)
+                    .map(|_| {
+                        state =
+                            state.wrapping_mul(1103515245).wrapping_add(12345);
+                        u8::from(state >> 31 == 1)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn record(frames: &[Vec<u8>]) -> Vec<u8>
+    {
+        let mut writer = HistoryWriter::new(Vec::new(), 5, 3, 4).unwrap();
+        for frame in frames {
+            writer.add_frame(frame).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn every_generation_reads_back()
+    {
+        let frames = frames();
+        let mut reader =
+            HistoryReader::new(Cursor::new(record(&frames))).unwrap();
+        assert_eq!((reader.width, reader.height, reader.len()), (5, 3, 10));
+        // Out of order, so that each seek starts from a keyframe afresh.
+        for generation in [6, 0, 9, 3, 4, 1, 8, 7, 2, 5] {
+            assert_eq!(reader.frame(generation).unwrap(), frames[generation]);
+        }
+        assert!(reader.frame(10).is_err());
+    }
+
+    #[test]
+    fn live_states_are_stored_as_one()
+    {
+        let mut frames = frames();
+        frames[0][0] = 7;
+        frames[1][0] = 3;
+        let mut reader =
+            HistoryReader::new(Cursor::new(record(&frames))).unwrap();
+        assert_eq!(reader.frame(0).unwrap()[0], 1);
+        assert_eq!(reader.frame(1).unwrap()[0], 1);
+    }
+
+    #[test]
+    fn bad_headers_are_rejected()
+    {
+        let bytes = record(&frames());
+        let damaged = |at: usize, value: &[u8]| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + value.len()].copy_from_slice(value);
+            HistoryReader::new(Cursor::new(bytes))
+                .err()
+                .map(|e| e.kind())
+        };
+        let invalid = Some(io::ErrorKind::InvalidData);
+        assert_eq!(damaged(0, b"LIFEHISX"), invalid);
+        assert_eq!(damaged(8, &2u16.to_le_bytes()), invalid);
+        assert_eq!(damaged(10, &u32::MAX.to_le_bytes()), invalid);
+        assert_eq!(damaged(18, &0u32.to_le_bytes()), invalid);
+        // The first keyframe offset, pointing past the index.
+        let index = bytes.len() - 16 - 3 * 8;
+        assert_eq!(damaged(index, &u64::MAX.to_le_bytes()), invalid);
+        // The generation count, which no longer matches the index.
+        assert_eq!(damaged(bytes.len() - 16, &99u64.to_le_bytes()), invalid);
+
+        assert!(HistoryReader::new(Cursor::new(&bytes[..30])).is_err());
+    }
+}
@@ -1,6 +1,9 @@
+/// Grows the square grid when any cell differing from `background` gets
+/// within a few cells of the edge; new cells take the background state.
 pub fn extend_flat_grid_to_square64(
-    grid: &Vec<u8>,
+    grid: &[u8],
     width: usize,
+    background: u8,
     out_buf: &mut Vec<u8>,
 ) -> usize
 {
@@ -16,15 +19,15 @@ pub fn extend_flat_grid_to_square64(
         .chunks(width)
         .skip(MARGIN)
         .take(height - 2 * MARGIN)
-        .any(|row| row[..MARGIN].iter().any(|&v| v == 1));
+        .any(|row| row[..MARGIN].iter().any(|&v| v != background));
     let right_near = grid
         .chunks(width)
         .skip(MARGIN)
         .take(height - 2 * MARGIN)
-        .any(|row| row[width - MARGIN..].iter().any(|&v| v == 1));
+        .any(|row| row[width - MARGIN..].iter().any(|&v| v != background));
 
-    let near_edge = top_band.iter().any(|&v| v == 1)
-        || bot_band.iter().any(|&v| v == 1)
+    let near_edge = top_band.iter().any(|&v| v != background)
+        || bot_band.iter().any(|&v| v != background)
         || left_near
         || right_near;
 
@@ -34,12 +37,12 @@ pub fn extend_flat_grid_to_square64(
 
     let base_pad = 1;
     let intermediate = width + 2 * base_pad;
-    let new_size = ((intermediate + 63) / 64) * 64;
+    let new_size = intermediate.div_ceil(64) * 64;
     let extra = new_size - intermediate;
     let pad_each = base_pad + (extra / 2);
 
     out_buf.clear();
-    out_buf.resize(new_size * new_size, 0);
+    out_buf.resize(new_size * new_size, background);
 
     for (row_idx, row) in grid.chunks_exact(width).enumerate() {
         let dst = (row_idx + pad_each) * new_size + pad_each;
@@ -48,3 +51,23 @@ pub fn extend_flat_grid_to_square64(
 
     new_size
 }
+
+/// Sets the border, `margin` cells deep, of a padded flat buffer to
+/// `value`, so the finite engines see the current background state beyond
+/// the grid edge.
+pub fn fill_border(
+    padded: &mut [u8],
+    padded_width: usize,
+    margin: usize,
+    value: u8,
+)
+{
+    let padded_height = padded.len() / padded_width;
+    padded[..margin * padded_width].fill(value);
+    padded[(padded_height - margin) * padded_width..].fill(value);
+    for y in margin..padded_height - margin {
+        let row = &mut padded[y * padded_width..(y + 1) * padded_width];
+        row[..margin].fill(value);
+        row[padded_width - margin..].fill(value);
+    }
+}
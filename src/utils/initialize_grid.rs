@@ -1,109 +1,491 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    process,
+    fs,
+    io::{self, Read},
+    path::Path,
     str::FromStr,
 };
 
-pub fn parse_arg<T: FromStr>(args: &[String], index: usize, name: &str) -> T
+use crate::utils::{
+    alphabet::Alphabet,
+    apgcode::{decode_apgcode, is_apgcode},
+    error::{LoadError, MAX_SIDE},
+    library::find_pattern,
+    mask::Mask,
+    netpbm::{is_netpbm, parse_netpbm},
+    pattern::{parse_pattern, Pattern, PatternFormat},
+    rule::{Rule, RuleMap},
+    scene::{is_scene_path, Scene},
+    transform::pad_grid_to_multiple_of_64,
+};
+
+pub fn parse_arg<T: FromStr>(
+    args: &[String],
+    index: usize,
+    name: &str,
+) -> Result<T, LoadError>
 where
     T::Err: std::fmt::Display,
 {
-    if args.len() <= index {
-        eprintln!("Missing argument: '{}'", name);
-        process::exit(1);
-    }
-    let val = &args[index];
-    val.parse::<T>().unwrap_or_else(|e| {
-        eprintln!("Invalid {} ('{}'): {}", name, val, e);
-        process::exit(1);
+    let Some(val) = args.get(index) else {
+        return Err(LoadError::MissingArgument {
+            name: name.to_string(),
+        });
+    };
+    val.parse::<T>().map_err(|e| LoadError::InvalidArgument {
+        name: name.to_string(),
+        value: val.clone(),
+        message: e.to_string(),
     })
 }
 
-fn pad_grid_to_multiple_of_64(grid: Vec<Vec<bool>>) -> Vec<Vec<bool>>
+/// How plain-text grids and images are turned into cells.
+#[derive(Clone, Debug)]
+pub struct LoadOptions
 {
-    let height = grid.len();
-    let width = grid.first().map(|r| r.len()).unwrap_or(0);
+    /// Alphabet for plain-text grids; detected when `None`.
+    pub alphabet: Option<Alphabet>,
+    /// Brightness (0-255) from which PGM/PPM pixels count as live.
+    pub threshold: u8,
+    /// Side in pixels of the block read as one cell from an image.
+    pub scale: usize,
+}
 
-    let target_h = ((height + 63) / 64) * 64;
-    let target_w = ((width + 63) / 64) * 64;
+impl Default for LoadOptions
+{
+    fn default() -> Self
+    {
+        LoadOptions {
+            alphabet: None,
+            threshold: 128,
+            scale: 1,
+        }
+    }
+}
 
-    let new_size = target_h.max(target_w);
+fn into_lines(path: &str, bytes: Vec<u8>) -> Result<Vec<String>, LoadError>
+{
+    let text = String::from_utf8(bytes)
+        .map_err(|_| LoadError::invalid(path, "not valid UTF-8 text"))?;
+    Ok(text.lines().map(str::to_string).collect())
+}
 
-    let pad_vert = new_size - height;
-    let pad_horiz = new_size - width;
+/// Reads `path`, or standard input when it is `-`.
+fn read_bytes(path: &str) -> Result<Vec<u8>, LoadError>
+{
+    if path == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_err(|e| LoadError::io(path, e))?;
+        return Ok(bytes);
+    }
+    fs::read(path).map_err(|e| LoadError::io(path, e))
+}
 
-    let pad_top = pad_vert / 2;
-    let pad_bottom = pad_vert - pad_top;
-    let pad_left = pad_horiz / 2;
-    let pad_right = pad_horiz - pad_left;
+fn read_lines(path: &str) -> Result<Vec<String>, LoadError>
+{
+    let bytes = read_bytes(path)?;
+    into_lines(path, bytes)
+}
 
-    let mut out = Vec::with_capacity(new_size);
-    let dead_row = vec![false; new_size];
+enum Source
+{
+    Image(Vec<Vec<u8>>),
+    Text(Vec<String>),
+}
 
-    for _ in 0..pad_top {
-        out.push(dead_row.clone());
+/// Reads `path`, decoding it straight away if it is a Netpbm image.
+fn read_source(path: &str, options: &LoadOptions) -> Result<Source, LoadError>
+{
+    let bytes = read_bytes(path)?;
+    if !is_netpbm(&bytes) {
+        return Ok(Source::Text(into_lines(path, bytes)?));
     }
+    let cells = parse_netpbm(&bytes, options.threshold, options.scale)
+        .map_err(|e| LoadError::invalid(path, e))?;
+    check_size(path, cells.first().map_or(0, |r| r.len()), cells.len())?;
+    Ok(Source::Image(cells))
+}
 
-    for mut row in grid {
-        let mut new_row = Vec::with_capacity(new_size);
-        new_row.extend(std::iter::repeat(false).take(pad_left));
-        new_row.append(&mut row);
-        new_row.extend(std::iter::repeat(false).take(pad_right));
-        out.push(new_row);
+fn check_size(path: &str, width: usize, height: usize)
+    -> Result<(), LoadError>
+{
+    if width == 0 || height == 0 {
+        return Err(LoadError::Empty {
+            path: path.to_string(),
+        });
+    }
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(LoadError::Oversized {
+            path: path.to_string(),
+            width,
+            height,
+        });
     }
+    Ok(())
+}
 
-    for _ in 0..pad_bottom {
-        out.push(dead_row.clone());
+/// Reads a character grid whose first row is line `first_line` of `path`.
+/// Trailing blank lines are ignored; every other row must be as wide as the
+/// first, and `cell` maps each character to a value or rejects it.
+fn read_grid<T>(
+    path: &str,
+    rows: &[String],
+    first_line: usize,
+    cell: impl Fn(char) -> Option<T>,
+) -> Result<Vec<Vec<T>>, LoadError>
+{
+    let end = rows
+        .iter()
+        .rposition(|r| !r.is_empty())
+        .map_or(0, |p| p + 1);
+    let rows = &rows[..end];
+    let width = rows.first().map_or(0, |r| r.chars().count());
+    check_size(path, width, rows.len())?;
+
+    let mut grid = Vec::with_capacity(rows.len());
+    for (i, line) in rows.iter().enumerate() {
+        let line_no = first_line + i;
+        let row = line
+            .chars()
+            .enumerate()
+            .map(|(col, ch)| {
+                cell(ch).ok_or_else(|| LoadError::UnexpectedChar {
+                    path: path.to_string(),
+                    line: line_no,
+                    column: col + 1,
+                    found: ch,
+                })
+            })
+            .collect::<Result<Vec<T>, _>>()?;
+        if row.len() != width {
+            return Err(LoadError::Ragged {
+                path: path.to_string(),
+                line: line_no,
+                expected: width,
+                found: row.len(),
+            });
+        }
+        grid.push(row);
     }
+    Ok(grid)
+}
 
-    out
+fn parse_pattern_lines(
+    lines: &[String],
+    format: PatternFormat,
+    path: &str,
+) -> Result<Pattern, LoadError>
+{
+    let pattern = parse_pattern(&lines.join("\n"), format)
+        .map_err(|e| LoadError::invalid(path, e))?;
+    check_size(path, pattern.width, pattern.height)?;
+    Ok(pattern)
 }
 
-pub fn load_grid(path: &str) -> Vec<Vec<bool>>
+fn to_bool_grid(cells: Vec<Vec<u8>>) -> Vec<Vec<bool>>
 {
-    const ALIVE: char = 'X';
-    const DEAD: char = '.';
+    cells
+        .into_iter()
+        .map(|row| row.into_iter().map(|c| c != 0).collect())
+        .collect()
+}
 
-    let file = File::open(path).unwrap_or_else(|e| {
-        eprintln!("Failed to open {}: {}", path, e);
-        process::exit(1);
-    });
-    let reader = BufReader::new(file);
+/// True for the `!` and `//` comment lines allowed in plain-text grids.
+fn is_comment(line: &str) -> bool
+{
+    let line = line.trim_start();
+    line.starts_with('!') || line.starts_with("//")
+}
 
-    let lines: Vec<String> = reader
-        .lines()
-        .map(|r| {
-            r.unwrap_or_else(|e| {
-                eprintln!("I/O error: {}", e);
-                process::exit(1)
-            })
-        })
+/// Reads a plain-text grid, skipping comment lines and trailing
+/// whitespace. Rows shorter than the widest are padded with dead cells.
+/// Without an explicit alphabet, the first common one that fits is used.
+fn read_cells(
+    path: &str,
+    lines: &[String],
+    alphabet: Option<&Alphabet>,
+) -> Result<Vec<Vec<u8>>, LoadError>
+{
+    let mut rows: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !is_comment(l))
+        .map(|(i, l)| (i + 1, l.trim_end()))
         .collect();
+    while rows.last().is_some_and(|(_, r)| r.is_empty()) {
+        rows.pop();
+    }
+    let alphabet = match alphabet {
+        Some(a) => a.clone(),
+        None => {
+            let text: Vec<String> =
+                rows.iter().map(|(_, r)| r.to_string()).collect();
+            Alphabet::detect(&text)
+        }
+    };
 
-    let max_w = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+    let width = rows.iter().map(|(_, r)| r.chars().count()).max();
+    check_size(path, width.unwrap_or(0), rows.len())?;
 
-    let grid: Vec<Vec<bool>> = lines
-        .into_iter()
-        .map(|line| {
-            let mut row = Vec::with_capacity(max_w);
-            for ch in line.chars().take(max_w) {
-                row.push(match ch {
-                    ALIVE => true,
-                    DEAD => false,
-                    other => {
-                        eprintln!(
-                            "Unexpected character '{}' in {}",
-                            other, path
-                        );
-                        process::exit(1);
-                    }
-                });
-            }
-            row.resize(max_w, false);
-            row
-        })
-        .collect();
+    let mut grid = Vec::with_capacity(rows.len());
+    for (line, row) in rows {
+        let mut cells = row
+            .chars()
+            .enumerate()
+            .map(|(col, ch)| {
+                alphabet.state(ch).ok_or_else(|| LoadError::UnexpectedChar {
+                    path: path.to_string(),
+                    line,
+                    column: col + 1,
+                    found: ch,
+                })
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        cells.resize(width.unwrap_or(0), 0);
+        grid.push(cells);
+    }
+    Ok(grid)
+}
+
+/// Loads a pattern along with the rule it names, if any. Netpbm images,
+/// RLE, plaintext `.cells`, Life 1.05/1.06 and macrocell files are
+/// detected from the contents; anything else is read as a plain-text grid,
+/// in `options.alphabet` if one is given. A path that does not exist but
+/// reads as an apgcode (`xs4_33`) is decoded instead, one that names a
+/// pattern in the built-in library (`glider`) loads that, and `-` reads
+/// from standard input. `.scene` and `.toml` files are scenes composed
+/// from other patterns.
+pub fn load_pattern(
+    path: &str,
+    options: &LoadOptions,
+) -> Result<(Vec<Vec<bool>>, Option<Rule>), LoadError>
+{
+    prepare_pattern(path, read_pattern(path, options)?)
+}
+
+/// Pads a pattern from read_pattern for the engines and parses the rule it
+/// names.
+pub fn prepare_pattern(
+    path: &str,
+    pattern: Pattern,
+) -> Result<(Vec<Vec<bool>>, Option<Rule>), LoadError>
+{
+    let rule = pattern
+        .life_rule()
+        .transpose()
+        .map_err(|e| LoadError::invalid(path, e))?;
+    Ok((
+        pad_grid_to_multiple_of_64(to_bool_grid(pattern.cells)),
+        rule,
+    ))
+}
+
+/// Reads a pattern as load_pattern does, but without padding it for the
+/// engines, and with whatever rule, name, author and comments the source
+/// gives.
+pub fn read_pattern(
+    path: &str,
+    options: &LoadOptions,
+) -> Result<Pattern, LoadError>
+{
+    read_nested_pattern(path, options, 0)
+}
+
+/// How deep scenes may include other scenes, which also stops cycles.
+const MAX_SCENE_DEPTH: usize = 8;
+
+/// read_pattern for a source `depth` scenes down.
+fn read_nested_pattern(
+    path: &str,
+    options: &LoadOptions,
+    depth: usize,
+) -> Result<Pattern, LoadError>
+{
+    if is_apgcode(path) && !Path::new(path).exists() {
+        let pattern =
+            decode_apgcode(path).map_err(|e| LoadError::invalid(path, e))?;
+        check_size(path, pattern.width, pattern.height)?;
+        return Ok(pattern);
+    }
+    if let Some(entry) =
+        find_pattern(path).filter(|_| !Path::new(path).exists())
+    {
+        return Ok(entry.pattern());
+    }
+    if is_scene_path(path) {
+        if depth >= MAX_SCENE_DEPTH {
+            return Err(LoadError::invalid(path, "scenes nested too deeply"));
+        }
+        let scene = read_lines(path)?
+            .join("\n")
+            .parse::<Scene>()
+            .map_err(|e| LoadError::invalid(path, e))?;
+        check_size(path, scene.width, scene.height)?;
+        let cells = scene.compose(path, |source| {
+            Ok(read_nested_pattern(source, options, depth + 1)?.cells)
+        })?;
+        return Ok(Pattern {
+            rule: scene.rule.map(|r| r.to_string()),
+            ..Pattern::from_cells(cells)
+        });
+    }
+
+    let lines = match read_source(path, options)? {
+        Source::Image(cells) => return Ok(Pattern::from_cells(cells)),
+        Source::Text(lines) => lines,
+    };
+    let alphabet = options.alphabet.as_ref();
+    let format = PatternFormat::detect(&lines.join("\n"));
+    if alphabet.is_none() && format != PatternFormat::Text {
+        return parse_pattern_lines(&lines, format, path);
+    }
+
+    Ok(Pattern::from_cells(read_cells(path, &lines, alphabet)?))
+}
+
+pub fn load_grid(path: &str) -> Result<Vec<Vec<bool>>, LoadError>
+{
+    Ok(load_pattern(path, &LoadOptions::default())?.0)
+}
+
+/// Loads a coloured pattern, with state N read as colour N. Plain-text
+/// grids default to `.` for dead, `X` for colour 1 and the digits `1`-`9`
+/// for explicit colours; multi-state RLE and macrocell files are accepted
+/// too, and images load as colour 1. States beyond `colours` are errors.
+pub fn load_colour_grid(
+    path: &str,
+    options: &LoadOptions,
+    colours: usize,
+) -> Result<Vec<Vec<u8>>, LoadError>
+{
+    let lines = match read_source(path, options)? {
+        Source::Image(cells) => return Ok(pad_grid_to_multiple_of_64(cells)),
+        Source::Text(lines) => lines,
+    };
+    let alphabet = options.alphabet.as_ref();
+    let format = PatternFormat::detect(&lines.join("\n"));
+    let grid = if alphabet.is_none() && format != PatternFormat::Text {
+        parse_pattern_lines(&lines, format, path)?.cells
+    } else {
+        read_cells(path, &lines, alphabet)?
+    };
+
+    if let Some(state) = grid.iter().flatten().find(|&&c| c as usize > colours)
+    {
+        return Err(LoadError::invalid(
+            path,
+            format!("state {} is beyond the {} colours", state, colours),
+        ));
+    }
+    Ok(pad_grid_to_multiple_of_64(grid))
+}
 
-    pad_grid_to_multiple_of_64(grid)
+/// Width and height of the cells of `pattern`, before any padding; maps
+/// and masks must match it, as padding can hide a mismatch.
+pub fn grid_size(pattern: &Pattern) -> (usize, usize)
+{
+    (
+        pattern.cells.first().map_or(0, |r| r.len()),
+        pattern.cells.len(),
+    )
+}
+
+/// Fails unless a map or mask read from `path` is exactly as large as the
+/// pattern it accompanies, whose unpadded size is `expected`.
+fn check_layout<T>(
+    path: &str,
+    grid: &[Vec<T>],
+    expected: (usize, usize),
+) -> Result<(), LoadError>
+{
+    let found = (grid.first().map_or(0, |r| r.len()), grid.len());
+    if found != expected {
+        return Err(LoadError::invalid(
+            path,
+            format!(
+                "is {}x{}, but the pattern is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Loads a rule map: `<char> = <rule>` legend lines followed by a grid of
+/// region characters laid out like the pattern it accompanies, which is
+/// `size` cells before padding. Padding added around the map uses the
+/// first rule of the legend.
+pub fn load_rule_map(
+    path: &str,
+    size: (usize, usize),
+) -> Result<RuleMap, LoadError>
+{
+    let lines = read_lines(path)?;
+
+    let mut legend: Vec<char> = Vec::new();
+    let mut rules: Vec<Rule> = Vec::new();
+    let mut header = 0;
+    for line in &lines {
+        let Some((key, rule)) = line.split_once('=') else {
+            break;
+        };
+        header += 1;
+        let mut chars = key.trim().chars();
+        let (Some(ch), None) = (chars.next(), chars.next()) else {
+            return Err(LoadError::invalid(
+                path,
+                format!("line {}: invalid region name '{}'", header, key),
+            ));
+        };
+        let rule = rule.parse::<Rule>().map_err(|e| {
+            LoadError::invalid(
+                path,
+                format!("line {}: invalid rule for '{}': {}", header, ch, e),
+            )
+        })?;
+        legend.push(ch);
+        rules.push(rule);
+    }
+
+    if rules.is_empty() {
+        return Err(LoadError::invalid(path, "no rules defined"));
+    }
+
+    let grid = read_grid(path, &lines[header..], header + 1, |ch| {
+        legend.iter().position(|&c| c == ch).map(|r| r as u8)
+    })?;
+    check_layout(path, &grid, size)?;
+
+    Ok(RuleMap {
+        rules,
+        regions: pad_grid_to_multiple_of_64(grid).concat(),
+    })
+}
+
+/// Loads an obstacle mask laid out like the pattern it accompanies, which
+/// is `size` cells before padding: `#` pins a cell dead, `+` pins it alive
+/// and `.` leaves it to the rule.
+pub fn load_mask(path: &str, size: (usize, usize)) -> Result<Mask, LoadError>
+{
+    const FREE: char = '.';
+    const WALL: char = '#';
+    const SOURCE: char = '+';
+
+    // 0 must mean "free" so that the padding added below pins nothing.
+    let lines = read_lines(path)?;
+    let grid = read_grid(path, &lines, 1, |ch| match ch {
+        FREE => Some(0u8),
+        WALL => Some(1),
+        SOURCE => Some(2),
+        _ => None,
+    })?;
+    check_layout(path, &grid, size)?;
+
+    let pins = pad_grid_to_multiple_of_64(grid).concat();
+    Ok(Mask {
+        keep: pins.iter().map(|&p| (p != 1) as u8).collect(),
+        force: pins.iter().map(|&p| (p == 2) as u8).collect(),
+    })
 }
@@ -0,0 +1,348 @@
+//! Built-in catalogue of well-known Conway's Life patterns, so the common
+//! ones can be named on the command line without a file.
+
+use std::collections::HashSet;
+
+use crate::utils::{
+    apgcode::step, pattern::Pattern, rle::parse_rle, rule::Rule,
+};
+
+/// What a pattern does, with the numbers that pin its behaviour down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind
+{
+    StillLife,
+    Oscillator
+    {
+        period: usize,
+    },
+    /// Moves by `displacement` (x, y) every `period` generations.
+    Spaceship
+    {
+        period: usize,
+        displacement: (i64, i64),
+    },
+    /// Returns to its starting state every `period` generations, plus one
+    /// glider that has been fired off.
+    Gun
+    {
+        period: usize,
+    },
+    /// Settles down after `lifespan` generations, leaving
+    /// `final_population` live cells (escaping gliders included).
+    Methuselah
+    {
+        lifespan: usize,
+        final_population: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LibraryPattern
+{
+    pub name: &'static str,
+    pub kind: Kind,
+    pub discoverer: Option<&'static str>,
+    pub year: Option<u16>,
+    rle: &'static str,
+}
+
+pub const LIBRARY: &[LibraryPattern] = &[
+    LibraryPattern {
+        name: "block",
+        kind: Kind::StillLife,
+        discoverer: None,
+        year: None,
+        rle: "2o$2o!",
+    },
+    LibraryPattern {
+        name: "beehive",
+        kind: Kind::StillLife,
+        discoverer: None,
+        year: None,
+        rle: "b2o$o2bo$b2o!",
+    },
+    LibraryPattern {
+        name: "blinker",
+        kind: Kind::Oscillator { period: 2 },
+        discoverer: Some("John Conway"),
+        year: Some(1969),
+        rle: "3o!",
+    },
+    LibraryPattern {
+        name: "toad",
+        kind: Kind::Oscillator { period: 2 },
+        discoverer: Some("Simon Norton"),
+        year: Some(1970),
+        rle: "b3o$3o!",
+    },
+    LibraryPattern {
+        name: "beacon",
+        kind: Kind::Oscillator { period: 2 },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "2o$2o$2b2o$2b2o!",
+    },
+    LibraryPattern {
+        name: "pulsar",
+        kind: Kind::Oscillator { period: 3 },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$\
+              o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    },
+    LibraryPattern {
+        name: "pentadecathlon",
+        kind: Kind::Oscillator { period: 15 },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "2bo4bo$2ob4ob2o$2bo4bo!",
+    },
+    LibraryPattern {
+        name: "glider",
+        kind: Kind::Spaceship {
+            period: 4,
+            displacement: (1, 1),
+        },
+        discoverer: Some("Richard K. Guy"),
+        year: Some(1969),
+        rle: "bo$2bo$3o!",
+    },
+    LibraryPattern {
+        name: "lwss",
+        kind: Kind::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "bo2bo$o$o3bo$4o!",
+    },
+    LibraryPattern {
+        name: "mwss",
+        kind: Kind::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "3bo$bo3bo$o$o4bo$5o!",
+    },
+    LibraryPattern {
+        name: "hwss",
+        kind: Kind::Spaceship {
+            period: 4,
+            displacement: (-2, 0),
+        },
+        discoverer: Some("John Conway"),
+        year: Some(1970),
+        rle: "3b2o$bo4bo$o$o5bo$6o!",
+    },
+    LibraryPattern {
+        name: "gosper-glider-gun",
+        kind: Kind::Gun { period: 30 },
+        discoverer: Some("Bill Gosper"),
+        year: Some(1970),
+        rle: "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$\
+              2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!",
+    },
+    LibraryPattern {
+        name: "r-pentomino",
+        kind: Kind::Methuselah {
+            lifespan: 1103,
+            final_population: 116,
+        },
+        discoverer: Some("John Conway"),
+        year: Some(1969),
+        rle: "b2o$2o$bo!",
+    },
+    LibraryPattern {
+        name: "acorn",
+        kind: Kind::Methuselah {
+            lifespan: 5206,
+            final_population: 633,
+        },
+        discoverer: Some("Charles Corderman"),
+        year: Some(1971),
+        rle: "bo$3bo$2o2b3o!",
+    },
+    LibraryPattern {
+        name: "diehard",
+        kind: Kind::Methuselah {
+            lifespan: 130,
+            final_population: 0,
+        },
+        discoverer: None,
+        year: None,
+        rle: "6bo$2o$bo3b3o!",
+    },
+];
+
+/// Looks a pattern up by name, ignoring case and treating spaces, `_` and
+/// `-` alike, so `Gosper_glider_gun` finds `gosper-glider-gun`.
+pub fn find_pattern(name: &str) -> Option<&'static LibraryPattern>
+{
+    let key = |s: &str| -> String {
+        s.chars()
+            .map(|c| match c {
+                ' ' | '_' => '-',
+                c => c.to_ascii_lowercase(),
+            })
+            .collect()
+    };
+    let name = key(name);
+    LIBRARY.iter().find(|p| key(p.name) == name)
+}
+
+impl LibraryPattern
+{
+    pub fn pattern(&self) -> Pattern
+    {
+        // The entries hold only the cells; the header sizes to fit them.
+        let rle = format!("x = 0, y = 0, rule = B3/S23\n{}", self.rle);
+        let pattern = parse_rle(&rle).expect("library RLE is valid");
+        Pattern {
+            name: Some(self.name.to_string()),
+            author: self.discoverer.map(str::to_string),
+            ..pattern
+        }
+    }
+
+    /// Period, for everything that repeats.
+    pub fn period(&self) -> Option<usize>
+    {
+        match self.kind {
+            Kind::StillLife => Some(1),
+            Kind::Oscillator { period }
+            | Kind::Spaceship { period, .. }
+            | Kind::Gun { period } => Some(period),
+            Kind::Methuselah { .. } => None,
+        }
+    }
+
+    /// Distance travelled per period; (0, 0) for anything that stays put.
+    pub fn displacement(&self) -> (i64, i64)
+    {
+        match self.kind {
+            Kind::Spaceship { displacement, .. } => displacement,
+            _ => (0, 0),
+        }
+    }
+
+    /// Runs the pattern and checks that it behaves as its metadata says.
+    pub fn verify(&self) -> Result<(), String>
+    {
+        let rule = Rule::conway();
+        let start: HashSet<(i64, i64)> =
+            self.pattern().coordinates().into_iter().collect();
+        let run = |generations: usize| {
+            (0..generations).fold(start.clone(), |cells, _| step(&cells, rule))
+        };
+
+        if let Kind::Methuselah {
+            lifespan,
+            final_population,
+        } = self.kind
+        {
+            let population = run(lifespan).len();
+            if population != final_population {
+                return Err(format!(
+                    "{}: population {} at generation {}, expected {}",
+                    self.name, population, lifespan, final_population
+                ));
+            }
+            return Ok(());
+        }
+
+        let period = self.period().unwrap_or(1);
+        let (dx, dy) = self.displacement();
+        let mut cells = start.clone();
+        for generation in 1..=period {
+            cells = step(&cells, rule);
+            let moved: HashSet<(i64, i64)> =
+                cells.iter().map(|&(x, y)| (x - dx, y - dy)).collect();
+            let back = match self.kind {
+                Kind::Gun { .. } => {
+                    start.is_subset(&cells) && cells.len() == start.len() + 5
+                }
+                _ => moved == start,
+            };
+            if back != (generation == period) {
+                return Err(format!(
+                    "{}: {} after {} generations",
+                    self.name,
+                    if back { "repeats" } else { "does not repeat" },
+                    generation
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{Kind, LIBRARY};
+
+    fn verify_every(kind: fn(&Kind) -> bool)
+    {
+        for entry in LIBRARY.iter().filter(|entry| kind(&entry.kind)) {
+            if let Err(e) = entry.verify() {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    fn verify_named(name: &str)
+    {
+        let entry = LIBRARY.iter().find(|entry| entry.name == name).unwrap();
+        if let Err(e) = entry.verify() {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn still_lifes()
+    {
+        verify_every(|kind| matches!(kind, Kind::StillLife));
+    }
+
+    #[test]
+    fn oscillators()
+    {
+        verify_every(|kind| matches!(kind, Kind::Oscillator { .. }));
+    }
+
+    #[test]
+    fn spaceships()
+    {
+        verify_every(|kind| matches!(kind, Kind::Spaceship { .. }));
+    }
+
+    #[test]
+    fn guns()
+    {
+        verify_every(|kind| matches!(kind, Kind::Gun { .. }));
+    }
+
+    #[test]
+    fn diehard()
+    {
+        verify_named("diehard");
+    }
+
+    #[test]
+    #[ignore = "runs for 1103 generations"]
+    fn r_pentomino()
+    {
+        verify_named("r-pentomino");
+    }
+
+    #[test]
+    #[ignore = "runs for 5206 generations"]
+    fn acorn()
+    {
+        verify_named("acorn");
+    }
+}
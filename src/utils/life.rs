@@ -0,0 +1,224 @@
+//! Life 1.05 (`#P` blocks of `.`/`*` rows) and Life 1.06 (one `x y`
+//! coordinate pair per live cell). Life 1.05 has no name line, as `#N`
+//! means the normal rules, so names go in a `#D Name:` description line.
+
+use crate::utils::{pattern::Pattern, rule::Rule};
+
+pub fn parse_life105(text: &str) -> Result<Pattern, String>
+{
+    let mut rule = None;
+    let mut name = None;
+    let mut comments = Vec::new();
+    let mut coords: Vec<(i64, i64)> = Vec::new();
+    let mut origin = (0i64, 0i64);
+    let mut row = 0i64;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(rest) = line.strip_prefix('#') {
+            let mut chars = rest.chars();
+            let tag = chars.next();
+            let body = chars.as_str().trim();
+            match tag {
+                Some('D') if body.starts_with("Name:") => {
+                    name = Some(body["Name:".len()..].trim().to_string())
+                }
+                Some('D') | Some('C') => comments.push(body.to_string()),
+                Some('N') => rule = Some("B3/S23".to_string()),
+                Some('R') => rule = Some(body.to_string()),
+                Some('P') => {
+                    let mut parts = body.split_whitespace().map(str::parse);
+                    let (Some(Ok(x)), Some(Ok(y))) =
+                        (parts.next(), parts.next())
+                    else {
+                        return Err(format!("line {}: malformed #P", i + 1));
+                    };
+                    origin = (x, y);
+                    row = 0;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        for (col, ch) in line.chars().enumerate() {
+            match ch {
+                '*' | 'O' => {
+                    let cell = origin
+                        .0
+                        .checked_add(col as i64)
+                        .zip(origin.1.checked_add(row));
+                    coords.push(cell.ok_or_else(|| {
+                        format!(
+                            "line {}: cell beyond the coordinate range",
+                            i + 1
+                        )
+                    })?)
+                }
+                '.' => {}
+                other => {
+                    return Err(format!(
+                        "line {}, column {}: unexpected character '{}'",
+                        i + 1,
+                        col + 1,
+                        other
+                    ))
+                }
+            }
+        }
+        row += 1;
+    }
+
+    Ok(Pattern {
+        rule,
+        name,
+        comments,
+        ..Pattern::from_coordinates(&coords)?
+    })
+}
+
+pub fn parse_life106(text: &str) -> Result<Pattern, String>
+{
+    let mut coords = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace().map(str::parse::<i64>);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => coords.push((x, y)),
+            _ => return Err(format!("line {}: expected 'x y'", i + 1)),
+        }
+    }
+    Pattern::from_coordinates(&coords)
+}
+
+pub fn to_life105(pattern: &Pattern) -> String
+{
+    let mut out = String::from("#Life 1.05\n");
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("#D Name: {}\n", name));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("#D {}\n", comment));
+    }
+    match &pattern.rule {
+        Some(rule) => {
+            let rule = rule
+                .parse::<Rule>()
+                .map_or(rule.clone(), |r| r.to_sb_string());
+            out.push_str(&format!("#R {}\n", rule));
+        }
+        None => out.push_str("#N\n"),
+    }
+    out.push_str(&format!(
+        "#P {} {}\n",
+        -(pattern.width as i64 / 2),
+        -(pattern.height as i64 / 2)
+    ));
+    for row in &pattern.cells {
+        let end = row.iter().rposition(|&c| c != 0).map_or(0, |p| p + 1);
+        out.extend(row[..end].iter().map(|&c| if c != 0 { '*' } else { '.' }));
+        if end == 0 {
+            out.push('.');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_life106(pattern: &Pattern) -> String
+{
+    let mut out = String::from("#Life 1.06\n");
+    let (dx, dy) = (pattern.width as i64 / 2, pattern.height as i64 / 2);
+    for (x, y) in pattern.coordinates() {
+        out.push_str(&format!("{} {}\n", x - dx, y - dy));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn life105_keeps_the_name()
+    {
+        let pattern = Pattern {
+            name: Some("glider".to_string()),
+            comments: vec!["found in 1969".to_string()],
+            ..Pattern::from_cells(vec![
+                vec![0, 1, 0],
+                vec![0, 0, 1],
+                vec![1, 1, 1],
+            ])
+        };
+        let back = parse_life105(&to_life105(&pattern)).unwrap();
+        assert_eq!(back.name, pattern.name);
+        assert_eq!(back.comments, pattern.comments);
+        assert_eq!(back.cells, pattern.cells);
+    }
+
+    fn glider() -> Pattern
+    {
+        Pattern::from_cells(vec![vec![0, 1, 0], vec![0, 0, 1], vec![1, 1, 1]])
+    }
+
+    #[test]
+    fn life105_round_trip()
+    {
+        let pattern = Pattern {
+            rule: Some("B36/S23".to_string()),
+            ..glider()
+        };
+        let text = to_life105(&pattern);
+        assert!(text.contains("#R 23/36\n#P -1 -1\n.*\n..*\n***\n"));
+        let back = parse_life105(&text).unwrap();
+        assert_eq!(back.cells, pattern.cells);
+        assert_eq!(
+            back.rule.unwrap().parse::<Rule>(),
+            "B36/S23".parse::<Rule>()
+        );
+
+        let back = parse_life105(&to_life105(&glider())).unwrap();
+        assert_eq!(back.rule.as_deref(), Some("B3/S23"));
+    }
+
+    #[test]
+    fn life106_round_trip()
+    {
+        let text = to_life106(&glider());
+        assert_eq!(text, "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n");
+        assert_eq!(parse_life106(&text).unwrap().cells, glider().cells);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected()
+    {
+        assert!(parse_life105("#Life 1.05\n#P 0\n*\n").is_err());
+        assert!(parse_life105("#Life 1.05\n#P 0 0\n*x\n").is_err());
+        assert!(parse_life106("#Life 1.06\n0 0 0\n").is_err());
+        assert!(parse_life106("#Life 1.06\n0 zero\n").is_err());
+    }
+
+    #[test]
+    fn far_apart_cells_are_rejected_before_allocating()
+    {
+        let text = "#Life 1.06\n0 0\n3000000000 3000000000\n";
+        assert!(parse_life106(text).is_err());
+
+        let text = "#Life 1.05\n#P 0 0\n*\n#P 100000 0\n*\n";
+        assert!(parse_life105(text).is_err());
+    }
+
+    #[test]
+    fn extreme_coordinates_do_not_overflow()
+    {
+        let text = format!("#Life 1.06\n{} 0\n{} 0\n", i64::MIN, i64::MAX);
+        assert!(parse_life106(&text).is_err());
+
+        let text = format!("#Life 1.05\n#P {} 0\n.*\n", i64::MAX);
+        assert!(parse_life105(&text).is_err());
+    }
+}
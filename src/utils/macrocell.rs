@@ -0,0 +1,444 @@
+//! Golly macrocell (`.mc`) format: a hash-consed quadtree written bottom
+//! up, one node per line. Two-state patterns use 8x8 leaf bitmaps
+//! (`.`/`*` rows separated by `$`), multi-state ones use level-1 nodes
+//! listing four cell states. Node lines are `<level> <nw> <ne> <sw> <se>`,
+//! where children are 1-based line indices and 0 is an empty subtree; the
+//! last node is the root.
+
+use std::collections::HashMap;
+
+use crate::utils::{error::MAX_SIDE, pattern::Pattern};
+
+const HEADER: &str = "[M2] (game_of_life)";
+
+enum Node
+{
+    Leaf8([u8; 64]),
+    Level1([u8; 4]),
+    Inner
+    {
+        level: u32,
+        children: [usize; 4],
+    },
+}
+
+impl Node
+{
+    fn level(&self) -> u32
+    {
+        match self {
+            Node::Leaf8(_) => 3,
+            Node::Level1(_) => 1,
+            Node::Inner { level, .. } => *level,
+        }
+    }
+}
+
+pub fn is_macrocell(text: &str) -> bool
+{
+    text.trim_start().starts_with("[M2]")
+}
+
+fn parse_leaf(line: &str) -> Result<[u8; 64], String>
+{
+    let mut cells = [0u8; 64];
+    let (mut x, mut y) = (0usize, 0usize);
+    for ch in line.chars() {
+        match ch {
+            '.' => x += 1,
+            '*' => {
+                if x >= 8 || y >= 8 {
+                    return Err("leaf exceeds 8x8".to_string());
+                }
+                cells[y * 8 + x] = 1;
+                x += 1;
+            }
+            '$' => {
+                x = 0;
+                y += 1;
+            }
+            other => return Err(format!("unexpected character '{}'", other)),
+        }
+    }
+    Ok(cells)
+}
+
+/// Live extent `[min_x, min_y, max_x, max_y]` of `cells`, a square `side`
+/// cells across.
+fn cell_bounds(cells: &[u8], side: usize) -> Option<[i64; 4]>
+{
+    cells
+        .iter()
+        .enumerate()
+        .filter(|&(_, &c)| c != 0)
+        .map(|(i, _)| {
+            let (x, y) = ((i % side) as i64, (i / side) as i64);
+            [x, y, x, y]
+        })
+        .reduce(union)
+}
+
+fn union(a: [i64; 4], b: [i64; 4]) -> [i64; 4]
+{
+    [
+        a[0].min(b[0]),
+        a[1].min(b[1]),
+        a[2].max(b[2]),
+        a[3].max(b[3]),
+    ]
+}
+
+/// Live extent of every node relative to its top-left corner, None when it
+/// is empty. Children come before their parents, so one pass will do, and
+/// the tree never has to be expanded to measure it.
+fn bounds(nodes: &[Node]) -> Vec<Option<[i64; 4]>>
+{
+    let mut bounds: Vec<Option<[i64; 4]>> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let extent = match node {
+            Node::Leaf8(cells) => cell_bounds(cells, 8),
+            Node::Level1(cells) => cell_bounds(cells, 2),
+            Node::Inner { level, children } => {
+                let half = 1i64 << (level - 1);
+                children
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &child)| child != 0)
+                    .filter_map(|(i, &child)| {
+                        let [x0, y0, x1, y1] = bounds[child - 1]?;
+                        let dx = (i % 2) as i64 * half;
+                        let dy = (i / 2) as i64 * half;
+                        Some([x0 + dx, y0 + dy, x1 + dx, y1 + dy])
+                    })
+                    .reduce(union)
+            }
+        };
+        bounds.push(extent);
+    }
+    bounds
+}
+
+/// Writes the state of every live cell under `index`, whose top-left
+/// corner is at (`x`, `y`) in `grid`, into `grid`.
+fn paint(nodes: &[Node], index: usize, x: i64, y: i64, grid: &mut [Vec<u8>])
+{
+    if index == 0 {
+        return;
+    }
+    let mut set = |cx: i64, cy: i64, c: u8| {
+        grid[cy as usize][cx as usize] = c;
+    };
+    match &nodes[index - 1] {
+        Node::Leaf8(cells) => {
+            for (i, &c) in cells.iter().enumerate() {
+                if c != 0 {
+                    set(x + (i % 8) as i64, y + (i / 8) as i64, c);
+                }
+            }
+        }
+        Node::Level1(cells) => {
+            for (i, &c) in cells.iter().enumerate() {
+                if c != 0 {
+                    set(x + (i % 2) as i64, y + (i / 2) as i64, c);
+                }
+            }
+        }
+        Node::Inner { level, children } => {
+            let half = 1i64 << (level - 1);
+            for (i, &child) in children.iter().enumerate() {
+                let cx = x + (i % 2) as i64 * half;
+                let cy = y + (i / 2) as i64 * half;
+                paint(nodes, child, cx, cy, grid);
+            }
+        }
+    }
+}
+
+pub fn parse_macrocell(text: &str) -> Result<Pattern, String>
+{
+    let mut pattern = Pattern::default();
+    let mut nodes: Vec<Node> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let at = |e: String| format!("line {}: {}", i + 1, e);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('[') {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix('#') {
+            let mut chars = rest.chars();
+            let tag = chars.next();
+            let body = chars.as_str().trim().to_string();
+            match tag {
+                Some('R') => pattern.rule = Some(body),
+                Some('N') => pattern.name = Some(body),
+                Some('O') => pattern.author = Some(body),
+                Some('C') | Some('D') => pattern.comments.push(body),
+                _ => {}
+            }
+            continue;
+        }
+        if line.starts_with(['.', '*', '$']) {
+            nodes.push(Node::Leaf8(parse_leaf(line).map_err(at)?));
+            continue;
+        }
+
+        let fields = line
+            .split_whitespace()
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| at(e.to_string()))?;
+        let [level, a, b, c, d] = fields[..] else {
+            return Err(at("expected '<level> <nw> <ne> <sw> <se>'".into()));
+        };
+        if !(1..=63).contains(&level) {
+            return Err(at(format!("level {} is outside 1..=63", level)));
+        }
+        if level == 1 {
+            let mut states = [0u8; 4];
+            for (state, s) in states.iter_mut().zip([a, b, c, d]) {
+                *state = u8::try_from(s)
+                    .map_err(|_| at(format!("state {} is over 255", s)))?;
+            }
+            nodes.push(Node::Level1(states));
+            continue;
+        }
+        let children = [a, b, c, d];
+        for &child in &children {
+            if child > nodes.len() {
+                return Err(at(format!("child {} not defined yet", child)));
+            }
+            if child != 0 && nodes[child - 1].level() + 1 != level as u32 {
+                return Err(at(format!("child {} has the wrong level", child)));
+            }
+        }
+        nodes.push(Node::Inner {
+            level: level as u32,
+            children,
+        });
+    }
+
+    let extent = bounds(&nodes).last().copied().flatten();
+    let bounded = match extent {
+        Some([x0, y0, x1, y1]) => {
+            let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+            if width > MAX_SIDE as i64 || height > MAX_SIDE as i64 {
+                return Err(format!(
+                    "pattern is {}x{}, larger than the {}x{} limit",
+                    width, height, MAX_SIDE, MAX_SIDE
+                ));
+            }
+            let mut cells = vec![vec![0u8; width as usize]; height as usize];
+            paint(&nodes, nodes.len(), -x0, -y0, &mut cells);
+            Pattern::from_cells(cells)
+        }
+        None => Pattern::from_cells(Vec::new()),
+    };
+
+    Ok(Pattern {
+        width: bounded.width,
+        height: bounded.height,
+        cells: bounded.cells,
+        ..pattern
+    })
+}
+
+struct Builder<'a>
+{
+    cells: &'a [Vec<u8>],
+    multi_state: bool,
+    lines: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+impl Builder<'_>
+{
+    fn cell(&self, x: usize, y: usize) -> u8
+    {
+        self.cells
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn intern(&mut self, line: String) -> usize
+    {
+        if let Some(&i) = self.index.get(&line) {
+            return i;
+        }
+        self.lines.push(line.clone());
+        let i = self.lines.len();
+        self.index.insert(line, i);
+        i
+    }
+
+    fn build(&mut self, x: usize, y: usize, level: u32) -> usize
+    {
+        if !self.multi_state && level == 3 {
+            let mut rows = Vec::with_capacity(8);
+            for dy in 0..8 {
+                let row: String = (0..8)
+                    .map(|dx| {
+                        if self.cell(x + dx, y + dy) != 0 {
+                            '*'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                rows.push(row.trim_end_matches('.').to_string());
+            }
+            while rows.last().is_some_and(|r| r.is_empty()) {
+                rows.pop();
+            }
+            if rows.is_empty() {
+                return 0;
+            }
+            let line = rows.iter().map(|r| format!("{}$", r)).collect();
+            return self.intern(line);
+        }
+        if level == 1 {
+            let states = [
+                self.cell(x, y),
+                self.cell(x + 1, y),
+                self.cell(x, y + 1),
+                self.cell(x + 1, y + 1),
+            ];
+            if states == [0; 4] {
+                return 0;
+            }
+            let [a, b, c, d] = states;
+            return self.intern(format!("1 {} {} {} {}", a, b, c, d));
+        }
+
+        let half = 1usize << (level - 1);
+        let children = [
+            self.build(x, y, level - 1),
+            self.build(x + half, y, level - 1),
+            self.build(x, y + half, level - 1),
+            self.build(x + half, y + half, level - 1),
+        ];
+        if children == [0; 4] {
+            return 0;
+        }
+        let [a, b, c, d] = children;
+        self.intern(format!("{} {} {} {} {}", level, a, b, c, d))
+    }
+}
+
+pub fn to_macrocell(pattern: &Pattern) -> String
+{
+    let multi_state = pattern.cells.iter().flatten().any(|&c| c > 1);
+    let size = pattern.width.max(pattern.height).max(8);
+    let level = size.next_power_of_two().trailing_zeros();
+
+    let mut builder = Builder {
+        cells: &pattern.cells,
+        multi_state,
+        lines: Vec::new(),
+        index: HashMap::new(),
+    };
+    let root = builder.build(0, 0, level);
+
+    let mut out = format!("{}\n", HEADER);
+    if let Some(rule) = &pattern.rule {
+        out.push_str(&format!("#R {}\n", rule));
+    }
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("#N {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("#O {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("#C {}\n", comment));
+    }
+    for line in &builder.lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if root == 0 {
+        // An empty universe still needs a root node.
+        out.push_str("$\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn two_state_round_trip()
+    {
+        let pattern = Pattern {
+            rule: Some("B3/S23".to_string()),
+            name: Some("glider".to_string()),
+            ..Pattern::from_cells(vec![
+                vec![0, 1, 0],
+                vec![0, 0, 1],
+                vec![1, 1, 1],
+            ])
+        };
+        let text = to_macrocell(&pattern);
+        assert_eq!(
+            text,
+            "[M2] (game_of_life)\n#R B3/S23\n#N glider\n.*$..*$***$\n"
+        );
+        assert_eq!(parse_macrocell(&text), Ok(pattern));
+    }
+
+    #[test]
+    fn multi_state_round_trip()
+    {
+        let pattern = Pattern::from_cells(vec![
+            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 255],
+        ]);
+        let text = to_macrocell(&pattern);
+        assert!(text.contains("\n1 1 0 0 0\n"));
+        assert_eq!(parse_macrocell(&text), Ok(pattern));
+    }
+
+    #[test]
+    fn leaves_far_apart_are_placed_by_their_parents()
+    {
+        // Two single cells in opposite corners of a 32x32 square.
+        let text = "[M2]\n*$\n.......$.......$.......$.......$\
+                    .......$.......$.......$.......*$\n\
+                    4 1 0 0 0\n4 0 0 0 2\n5 3 0 0 4\n";
+        let pattern = parse_macrocell(text).unwrap();
+        assert_eq!((pattern.width, pattern.height), (32, 32));
+        assert_eq!(pattern.cells[0][0], 1);
+        assert_eq!(pattern.cells[31][31], 1);
+        assert_eq!(pattern.coordinates().len(), 2);
+    }
+
+    #[test]
+    fn empty_universe_round_trip()
+    {
+        let text = to_macrocell(&Pattern::from_cells(Vec::new()));
+        assert!(text.ends_with("\n$\n"));
+        assert_eq!(parse_macrocell(&text).unwrap().cells.len(), 0);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected()
+    {
+        let cases = [
+            "[M2]\n1 0 256 0 0\n",
+            "[M2]\n0 0 0 0 0\n",
+            "[M2]\n2 1 0 0 0\n",
+            "[M2]\n*$\n5 1 0 0 0\n",
+            "[M2]\n.........*$\n",
+            "[M2]\n4 0 0 0\n",
+            "[M2]\n*x$\n",
+        ];
+        for text in cases {
+            assert!(parse_macrocell(text).is_err(), "{:?}", text);
+        }
+    }
+}
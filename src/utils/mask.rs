@@ -0,0 +1,62 @@
+/// Cells pinned regardless of the rule, in the flat layout: the next state
+/// of cell `i` becomes `(state & keep[i]) | force[i]`, so walls have
+/// `keep = 0` and sources have `force = 1`.
+#[derive(Clone)]
+pub struct Mask
+{
+    pub keep: Vec<u8>,
+    pub force: Vec<u8>,
+}
+
+/// Bitboard form of a Mask, applied one 64-cell word at a time.
+#[derive(Clone)]
+pub struct BitMask
+{
+    pub keep: Vec<Vec<u64>>,
+    pub force: Vec<Vec<u64>>,
+}
+
+impl Mask
+{
+    pub fn apply(&self, grid: &mut [u8])
+    {
+        for ((cell, &keep), &force) in
+            grid.iter_mut().zip(&self.keep).zip(&self.force)
+        {
+            *cell = (*cell & keep) | force;
+        }
+    }
+
+    pub fn to_bitmask(&self, width: usize) -> BitMask
+    {
+        let pack = |flat: &[u8]| -> Vec<Vec<u64>> {
+            flat.chunks(width)
+                .map(|row| {
+                    let mut words = vec![0u64; width / 64];
+                    for (col, &v) in row.iter().enumerate() {
+                        if v != 0 {
+                            words[col / 64] |= 1 << (col % 64);
+                        }
+                    }
+                    words
+                })
+                .collect()
+        };
+        BitMask {
+            keep: pack(&self.keep),
+            force: pack(&self.force),
+        }
+    }
+}
+
+impl BitMask
+{
+    pub fn apply(&self, board: &mut [Vec<u64>])
+    {
+        for (r, row) in board.iter_mut().enumerate() {
+            for (wi, word) in row.iter_mut().enumerate() {
+                *word = (*word & self.keep[r][wi]) | self.force[r][wi];
+            }
+        }
+    }
+}
@@ -1,5 +1,32 @@
+pub mod ages;
+pub mod alphabet;
+pub mod apgcode;
 pub mod benchmark;
+pub mod checkpoint;
 pub mod conversion;
+pub mod error;
+pub mod flat_rules;
+pub mod gif;
+pub mod history;
 pub mod infinite_grid;
 pub mod initialize_grid;
+pub mod library;
+pub mod life;
+pub mod macrocell;
+pub mod mask;
+pub mod neighbourhood;
+pub mod netpbm;
+pub mod objects;
+pub mod palette;
+pub mod pattern;
+pub mod plaintext;
+pub mod png;
 pub mod print_board;
+pub mod rle;
+pub mod rule;
+pub mod scene;
+pub mod schedule;
+pub mod snapshots;
+pub mod svg;
+pub mod transform;
+pub mod y4m;
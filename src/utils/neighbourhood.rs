@@ -0,0 +1,213 @@
+use std::{fs, ops::RangeInclusive, str::FromStr};
+
+use crate::utils::error::LoadError;
+
+/// Weighted neighbourhood with birth/survival conditions on the weighted
+/// sum of live cells, replacing the fixed Moore neighbourhood.
+///
+/// Files give `birth = ...` and `survival = ...` lines (comma-separated
+/// sums, `lo..hi` for inclusive ranges) followed by a square weight grid of
+/// odd size whose centre is the cell itself; `#` starts a comment.
+#[derive(Clone, Debug)]
+pub struct WeightedNeighbourhood
+{
+    size: usize,
+    weights: Vec<i32>,
+    birth: Vec<RangeInclusive<i32>>,
+    survival: Vec<RangeInclusive<i32>>,
+}
+
+/// Largest kernel side accepted, which bounds the padding around the grid.
+pub const MAX_KERNEL_SIZE: usize = 63;
+
+/// Largest spread between the smallest and largest weighted sum, which
+/// bounds the next-state table.
+const MAX_SUM_SPAN: i64 = 1 << 20;
+
+/// Kernel compiled for a padded flat buffer of a given width.
+pub struct CompiledNeighbourhood
+{
+    pub radius: usize,
+    pub offsets: Vec<isize>,
+    pub weights: Vec<i32>,
+    pub min_sum: i32,
+    /// Next-state table indexed by `cell * span + (sum - min_sum)`.
+    pub table: Vec<u8>,
+    pub span: usize,
+}
+
+impl WeightedNeighbourhood
+{
+    /// A `size`x`size` kernel with `weights` in reading order; `birth`
+    /// and `survival` list the weighted sums on which a cell is born or
+    /// survives.
+    pub fn new(
+        size: usize,
+        weights: Vec<i32>,
+        birth: Vec<RangeInclusive<i32>>,
+        survival: Vec<RangeInclusive<i32>>,
+    ) -> Result<WeightedNeighbourhood, String>
+    {
+        if size.is_multiple_of(2) || size > MAX_KERNEL_SIZE {
+            return Err(format!(
+                "kernel size must be odd and at most {}, got {}",
+                MAX_KERNEL_SIZE, size
+            ));
+        }
+        if weights.len() != size * size {
+            return Err(format!(
+                "a {}x{} kernel needs {} weights, got {}",
+                size,
+                size,
+                size * size,
+                weights.len()
+            ));
+        }
+        let spread: i64 = weights.iter().map(|&w| (w as i64).abs()).sum();
+        if spread >= MAX_SUM_SPAN {
+            return Err(format!(
+                "kernel weights are too large: sums span {}, at most {}",
+                spread, MAX_SUM_SPAN
+            ));
+        }
+        Ok(WeightedNeighbourhood {
+            size,
+            weights,
+            birth,
+            survival,
+        })
+    }
+
+    /// The Moore neighbourhood with B3/S23, for comparison with gol_two.
+    pub fn moore() -> WeightedNeighbourhood
+    {
+        WeightedNeighbourhood {
+            size: 3,
+            weights: vec![1, 1, 1, 1, 0, 1, 1, 1, 1],
+            birth: vec![3..=3],
+            survival: vec![2..=3],
+        }
+    }
+
+    pub fn radius(&self) -> usize
+    {
+        self.size / 2
+    }
+
+    pub fn compile(&self, padded_width: usize) -> CompiledNeighbourhood
+    {
+        let r = self.radius() as isize;
+        let pw = padded_width as isize;
+
+        let mut offsets = Vec::new();
+        let mut weights = Vec::new();
+        for (i, &w) in self.weights.iter().enumerate() {
+            if w == 0 {
+                continue;
+            }
+            let dy = (i / self.size) as isize - r;
+            let dx = (i % self.size) as isize - r;
+            offsets.push(dy * pw + dx);
+            weights.push(w);
+        }
+
+        let min_sum: i32 = weights.iter().filter(|&&w| w < 0).sum();
+        let max_sum: i32 = weights.iter().filter(|&&w| w > 0).sum();
+        let span = (max_sum - min_sum + 1) as usize;
+
+        let mut table = vec![0u8; 2 * span];
+        for sum in min_sum..=max_sum {
+            let i = (sum - min_sum) as usize;
+            table[i] = self.birth.iter().any(|r| r.contains(&sum)) as u8;
+            table[span + i] =
+                self.survival.iter().any(|r| r.contains(&sum)) as u8;
+        }
+
+        CompiledNeighbourhood {
+            radius: self.radius(),
+            offsets,
+            weights,
+            min_sum,
+            table,
+            span,
+        }
+    }
+}
+
+fn parse_sums(list: &str) -> Result<Vec<RangeInclusive<i32>>, String>
+{
+    let mut sums = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let sum = |n: &str| n.trim().parse::<i32>().map_err(|e| e.to_string());
+        match part.split_once("..") {
+            Some((lo, hi)) => sums.push(sum(lo)?..=sum(hi)?),
+            None => sums.push(sum(part)?..=sum(part)?),
+        }
+    }
+    Ok(sums)
+}
+
+impl FromStr for WeightedNeighbourhood
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WeightedNeighbourhood, String>
+    {
+        let mut birth = None;
+        let mut survival = None;
+        let mut rows: Vec<Vec<i32>> = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let sums = parse_sums(value)
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?;
+                match key.trim() {
+                    "birth" => birth = Some(sums),
+                    "survival" => survival = Some(sums),
+                    other => {
+                        return Err(format!(
+                            "line {}: unknown key '{}'",
+                            i + 1,
+                            other
+                        ))
+                    }
+                }
+                continue;
+            }
+            let row = line
+                .split_whitespace()
+                .map(|w| w.parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            rows.push(row);
+        }
+
+        let size = rows.len();
+        if let Some(row) = rows.iter().find(|r| r.len() != size) {
+            return Err(format!(
+                "kernel must be square: {} rows, but a row of {}",
+                size,
+                row.len()
+            ));
+        }
+
+        WeightedNeighbourhood::new(
+            size,
+            rows.concat(),
+            birth.ok_or("missing 'birth' line")?,
+            survival.ok_or("missing 'survival' line")?,
+        )
+    }
+}
+
+pub fn load_neighbourhood(
+    path: &str,
+) -> Result<WeightedNeighbourhood, LoadError>
+{
+    let text = fs::read_to_string(path).map_err(|e| LoadError::io(path, e))?;
+    text.parse().map_err(|e| LoadError::invalid(path, e))
+}
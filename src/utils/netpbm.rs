@@ -0,0 +1,243 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::utils::palette::Palette;
+
+pub fn write_pgm(path: &str, grid: &[f32], width: usize) -> io::Result<()>
+{
+    let height = grid.len() / width;
+    let mut out = BufWriter::new(File::create(path)?);
+
+    write!(out, "P5\n{} {}\n255\n", width, height)?;
+    let pixels: Vec<u8> = grid
+        .iter()
+        .map(|&v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect();
+    out.write_all(&pixels)?;
+    out.flush()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat
+{
+    /// Bitmap; live cells are 1 (black) bits.
+    Pbm,
+    /// Greyscale from black (dead) to white (highest state in the grid).
+    Pgm,
+    /// Colour, one palette entry per state.
+    Ppm,
+}
+
+impl ImageFormat
+{
+    pub fn from_extension(path: &str) -> Option<ImageFormat>
+    {
+        let (_, ext) = path.rsplit_once('.')?;
+        match ext.to_ascii_lowercase().as_str() {
+            "pbm" => Some(ImageFormat::Pbm),
+            "pgm" => Some(ImageFormat::Pgm),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a grid of cell states as a binary Netpbm image, each cell drawn
+/// as a `scale`x`scale` block. Any grid of small integers works, so age
+/// maps can be written the same way as multi-state patterns.
+pub fn format_cells_image(
+    cells: &[Vec<u8>],
+    format: ImageFormat,
+    scale: usize,
+    palette: &Palette,
+) -> Vec<u8>
+{
+    let height = cells.len() * scale;
+    let width = cells.first().map_or(0, |r| r.len()) * scale;
+    let max_state = cells.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+    let scaled_row = |row: &[u8], pixel: &dyn Fn(u8) -> Vec<u8>| {
+        row.iter()
+            .flat_map(|&c| pixel(c).repeat(scale))
+            .collect::<Vec<u8>>()
+    };
+
+    let (magic, maxval) = match format {
+        ImageFormat::Pbm => ("P4", ""),
+        ImageFormat::Pgm => ("P5", "255\n"),
+        ImageFormat::Ppm => ("P6", "255\n"),
+    };
+    let mut out =
+        format!("{}\n{} {}\n{}", magic, width, height, maxval).into_bytes();
+
+    for row in cells {
+        let line = match format {
+            ImageFormat::Pbm => {
+                let bits = scaled_row(row, &|c| vec![(c != 0) as u8]);
+                bits.chunks(8)
+                    .map(|byte| {
+                        byte.iter()
+                            .enumerate()
+                            .fold(0u8, |acc, (i, &b)| acc | b << (7 - i))
+                    })
+                    .collect()
+            }
+            ImageFormat::Pgm => scaled_row(row, &|c| {
+                vec![(c as usize * 255 / max_state as usize) as u8]
+            }),
+            ImageFormat::Ppm => {
+                scaled_row(row, &|c| palette.colour(c).to_vec())
+            }
+        };
+        for _ in 0..scale {
+            out.extend_from_slice(&line);
+        }
+    }
+    out
+}
+
+pub fn write_cells_image(
+    path: &str,
+    cells: &[Vec<u8>],
+    format: ImageFormat,
+    scale: usize,
+    palette: &Palette,
+) -> io::Result<()>
+{
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&format_cells_image(cells, format, scale, palette))?;
+    out.flush()
+}
+
+/// True if `bytes` start with a `P1`-`P6` Netpbm magic number.
+pub fn is_netpbm(bytes: &[u8]) -> bool
+{
+    matches!(bytes, [b'P', b'1'..=b'6', next, ..] if next.is_ascii_whitespace())
+}
+
+/// Splits off the next whitespace-separated header token, skipping `#`
+/// comments.
+fn next_token<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], String>
+{
+    loop {
+        let start = bytes
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or("truncated header")?;
+        *bytes = &bytes[start..];
+        if bytes[0] == b'#' {
+            let end = bytes
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap_or(bytes.len());
+            *bytes = &bytes[end..];
+            continue;
+        }
+        let end = bytes
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .unwrap_or(bytes.len());
+        let (token, rest) = bytes.split_at(end);
+        *bytes = rest;
+        return Ok(token);
+    }
+}
+
+fn next_number(bytes: &mut &[u8]) -> Result<usize, String>
+{
+    let token = next_token(bytes)?;
+    std::str::from_utf8(token)
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .ok_or_else(|| {
+            format!("expected a number, found '{}'", token.escape_ascii())
+        })
+}
+
+/// Decodes any Netpbm image into cells. Pixels at least as bright as
+/// `threshold` (0-255) are live, except in PBM where the 1 (black) bits
+/// are; this round-trips the images written above. Each `scale`x`scale`
+/// block becomes one cell, sampled at its top-left pixel.
+pub fn parse_netpbm(
+    bytes: &[u8],
+    threshold: u8,
+    scale: usize,
+) -> Result<Vec<Vec<u8>>, String>
+{
+    if !is_netpbm(bytes) {
+        return Err("not a Netpbm image".to_string());
+    }
+    let kind = bytes[1] - b'0';
+    let mut rest = &bytes[2..];
+    let width = next_number(&mut rest)?;
+    let height = next_number(&mut rest)?;
+    let maxval = match kind {
+        1 | 4 => 1,
+        _ => next_number(&mut rest)?,
+    };
+    if !(1..=65535).contains(&maxval) {
+        return Err(format!("maxval {} out of range", maxval));
+    }
+    let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+    let samples = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels))
+        .ok_or("image size overflows")?;
+
+    // Raw samples, 0..=maxval; PBM bits are 0/1 with 1 meaning live.
+    // Every check against the bytes left comes before allocating, so the
+    // file, not its header, bounds the image.
+    let values: Vec<usize> = match kind {
+        1..=3 if rest.len() < samples => {
+            return Err("truncated pixel data".to_string())
+        }
+        1..=3 => (0..samples)
+            .map(|_| next_number(&mut rest))
+            .collect::<Result<_, _>>()?,
+        4 => {
+            rest = rest.get(1..).ok_or("truncated pixel data")?;
+            let stride = width.div_ceil(8);
+            if stride.checked_mul(height).is_none_or(|n| rest.len() < n) {
+                return Err("truncated pixel data".to_string());
+            }
+            (0..height)
+                .flat_map(|y| {
+                    let row = &rest[y * stride..];
+                    (0..width).map(move |x| {
+                        ((row[x / 8] >> (7 - x % 8)) & 1) as usize
+                    })
+                })
+                .collect()
+        }
+        _ => {
+            rest = rest.get(1..).ok_or("truncated pixel data")?;
+            let size = if maxval < 256 { 1 } else { 2 };
+            if samples.checked_mul(size).is_none_or(|n| rest.len() < n) {
+                return Err("truncated pixel data".to_string());
+            }
+            rest.chunks(size)
+                .take(samples)
+                .map(|s| s.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+                .collect()
+        }
+    };
+
+    let live = |x: usize, y: usize| {
+        let i = (y * width + x) * channels;
+        if kind == 1 || kind == 4 {
+            return values[i] != 0;
+        }
+        let sum: usize = values[i..i + channels].iter().sum();
+        sum * 255 / (channels * maxval) >= threshold as usize
+    };
+    let scale = scale.max(1);
+    Ok((0..height / scale)
+        .map(|y| {
+            (0..width / scale)
+                .map(|x| live(x * scale, y * scale) as u8)
+                .collect()
+        })
+        .collect())
+}
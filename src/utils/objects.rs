@@ -0,0 +1,50 @@
+use crate::utils::png::Region;
+
+/// Bounding boxes of the separate objects in a grid, where an object is a
+/// group of live cells connected through their eight neighbours. Boxes are
+/// in reading order of each object's first cell.
+pub fn find_objects(cells: &[Vec<u8>]) -> Vec<Region>
+{
+    let height = cells.len();
+    let width = cells.first().map_or(0, |r| r.len());
+    let mut seen = vec![false; width * height];
+    let mut objects = Vec::new();
+    let mut stack = Vec::new();
+
+    for y0 in 0..height {
+        for x0 in 0..width {
+            if cells[y0][x0] == 0 || seen[y0 * width + x0] {
+                continue;
+            }
+            seen[y0 * width + x0] = true;
+            stack.push((x0, y0));
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (x0, y0, x0, y0);
+
+            while let Some((x, y)) = stack.pop() {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+                let rows = cells.iter().enumerate().take(y + 2);
+                for (ny, row) in rows.skip(y.saturating_sub(1)) {
+                    let near = row.iter().enumerate().take(x + 2);
+                    for (nx, &c) in near.skip(x.saturating_sub(1)) {
+                        let i = ny * width + nx;
+                        if c != 0 && !seen[i] {
+                            seen[i] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            objects.push(Region {
+                x: min_x,
+                y: min_y,
+                width: max_x - min_x + 1,
+                height: max_y - min_y + 1,
+            });
+        }
+    }
+    objects
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+/// Colours for each cell state in PPM and PNG output. States past the end of
+/// the palette cycle through the live entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette
+{
+    colours: Vec<[u8; 3]>,
+}
+
+impl Palette
+{
+    pub fn colour(&self, state: u8) -> [u8; 3]
+    {
+        let live = self.colours.len() - 1;
+        match state as usize {
+            0 => self.colours[0],
+            s => self.colours[1 + (s - 1) % live],
+        }
+    }
+}
+
+/// Black background, white for two-state patterns, then the primaries and
+/// secondaries for further states.
+impl Default for Palette
+{
+    fn default() -> Self
+    {
+        Palette {
+            colours: vec![
+                [0, 0, 0],
+                [255, 255, 255],
+                [255, 0, 0],
+                [0, 255, 0],
+                [0, 0, 255],
+                [255, 255, 0],
+                [255, 0, 255],
+                [0, 255, 255],
+            ],
+        }
+    }
+}
+
+/// Parses an `RRGGBB` colour, with or without a leading `#`.
+pub fn parse_colour(s: &str) -> Result<[u8; 3], String>
+{
+    let hex = s.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("'{}' is not an RRGGBB colour", s))?;
+    Ok(value.to_be_bytes()[1..].try_into().unwrap())
+}
+
+/// Parses comma-separated `RRGGBB` colours, dead state first.
+impl FromStr for Palette
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let colours = s
+            .split(',')
+            .map(parse_colour)
+            .collect::<Result<Vec<[u8; 3]>, String>>()?;
+        if colours.len() < 2 {
+            return Err("a palette needs a dead and a live colour".to_string());
+        }
+        Ok(Palette { colours })
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::utils::{
+    error::MAX_SIDE, life, macrocell, plaintext, rle, rule::Rule,
+};
+
+/// Pattern with the metadata carried by the interchange formats; cells use
+/// the flat-layout states (0=dead, 1=alive, higher for multi-state rules).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pattern
+{
+    pub width: usize,
+    pub height: usize,
+    pub rule: Option<String>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+    pub cells: Vec<Vec<u8>>,
+}
+
+impl Pattern
+{
+    pub fn from_cells(cells: Vec<Vec<u8>>) -> Pattern
+    {
+        Pattern {
+            width: cells.first().map_or(0, |r| r.len()),
+            height: cells.len(),
+            cells,
+            ..Pattern::default()
+        }
+    }
+
+    /// Rule named by the pattern, if it is a Life-like rule; any `:T...`
+    /// bounded-grid suffix is ignored.
+    pub fn life_rule(&self) -> Option<Result<Rule, String>>
+    {
+        self.rule
+            .as_ref()
+            .map(|r| r.split(':').next().unwrap_or(r).parse::<Rule>())
+    }
+
+    /// Builds a pattern from live-cell coordinates, translated so the
+    /// bounding box starts at (0, 0). The box is measured before anything
+    /// is allocated, and must be no larger than MAX_SIDE a side.
+    pub fn from_coordinates(coords: &[(i64, i64)]) -> Result<Pattern, String>
+    {
+        let min_x = coords.iter().map(|c| c.0).min().unwrap_or(0);
+        let min_y = coords.iter().map(|c| c.1).min().unwrap_or(0);
+        let max_x = coords.iter().map(|c| c.0).max().unwrap_or(-1);
+        let max_y = coords.iter().map(|c| c.1).max().unwrap_or(-1);
+
+        // Spans of i64 coordinates can overflow an i64, but not an i128.
+        let side = |min: i64, max: i64| max as i128 - min as i128 + 1;
+        let (width, height) = (side(min_x, max_x), side(min_y, max_y));
+        if width > MAX_SIDE as i128 || height > MAX_SIDE as i128 {
+            return Err(format!(
+                "pattern is {}x{}, larger than the {}x{} limit",
+                width, height, MAX_SIDE, MAX_SIDE
+            ));
+        }
+        let (width, height) = (width as usize, height as usize);
+        let mut cells = vec![vec![0u8; width]; height];
+        for &(x, y) in coords {
+            cells[(y - min_y) as usize][(x - min_x) as usize] = 1;
+        }
+        Ok(Pattern::from_cells(cells))
+    }
+
+    /// Coordinates of every live cell, relative to the top-left corner.
+    pub fn coordinates(&self) -> Vec<(i64, i64)>
+    {
+        let mut coords = Vec::new();
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                if c != 0 {
+                    coords.push((x as i64, y as i64));
+                }
+            }
+        }
+        coords
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternFormat
+{
+    /// This project's `X`/`.` grid.
+    Text,
+    Rle,
+    /// Plaintext `.cells` with `!` comments and `O` cells.
+    Plaintext,
+    Life105,
+    Life106,
+    /// Golly's quadtree format, for huge or sparse patterns.
+    Macrocell,
+}
+
+impl PatternFormat
+{
+    pub fn from_name(name: &str) -> Option<PatternFormat>
+    {
+        match name.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Some(PatternFormat::Text),
+            "rle" => Some(PatternFormat::Rle),
+            "cells" | "plaintext" => Some(PatternFormat::Plaintext),
+            "life105" | "1.05" => Some(PatternFormat::Life105),
+            "life106" | "1.06" | "lif" | "life" => Some(PatternFormat::Life106),
+            "mc" | "macrocell" => Some(PatternFormat::Macrocell),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(path: &str) -> Option<PatternFormat>
+    {
+        let ext = Path::new(path).extension()?.to_str()?;
+        PatternFormat::from_name(ext)
+    }
+
+    /// Guesses the format from the file contents.
+    pub fn detect(text: &str) -> PatternFormat
+    {
+        let first = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("");
+        if macrocell::is_macrocell(text) {
+            PatternFormat::Macrocell
+        } else if first.starts_with("#Life 1.05") {
+            PatternFormat::Life105
+        } else if first.starts_with("#Life 1.06") {
+            PatternFormat::Life106
+        } else if rle::is_rle(text) {
+            PatternFormat::Rle
+        } else if plaintext::is_plaintext(text) {
+            PatternFormat::Plaintext
+        } else {
+            PatternFormat::Text
+        }
+    }
+}
+
+/// Parses any format except Text, whose loader lives in initialize_grid.
+pub fn parse_pattern(
+    text: &str,
+    format: PatternFormat,
+) -> Result<Pattern, String>
+{
+    match format {
+        PatternFormat::Rle => rle::parse_rle(text),
+        PatternFormat::Plaintext => plaintext::parse_plaintext(text),
+        PatternFormat::Life105 => life::parse_life105(text),
+        PatternFormat::Life106 => life::parse_life106(text),
+        PatternFormat::Macrocell => macrocell::parse_macrocell(text),
+        PatternFormat::Text => Err("use load_grid for X/. grids".to_string()),
+    }
+}
+
+pub fn format_pattern(pattern: &Pattern, format: PatternFormat) -> String
+{
+    match format {
+        PatternFormat::Text => pattern
+            .cells
+            .iter()
+            .map(|row| {
+                let mut line: String = row
+                    .iter()
+                    .map(|&c| if c != 0 { 'X' } else { '.' })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect(),
+        PatternFormat::Rle => rle::to_rle(pattern),
+        PatternFormat::Plaintext => plaintext::to_plaintext(pattern),
+        PatternFormat::Life105 => life::to_life105(pattern),
+        PatternFormat::Life106 => life::to_life106(pattern),
+        PatternFormat::Macrocell => macrocell::to_macrocell(pattern),
+    }
+}
+
+pub fn write_pattern(
+    path: &str,
+    pattern: &Pattern,
+    format: PatternFormat,
+) -> io::Result<()>
+{
+    let mut file = File::create(path)?;
+    file.write_all(format_pattern(pattern, format).as_bytes())
+}
@@ -0,0 +1,87 @@
+use crate::utils::pattern::Pattern;
+
+/// True if every non-comment line uses only `.` and `O` (or `*`), with at
+/// least one live cell; this separates `.cells` from the `X`/`.` grid
+/// format.
+pub fn is_plaintext(text: &str) -> bool
+{
+    let mut any_alive = false;
+    for line in text.lines().filter(|l| !l.starts_with('!')) {
+        for ch in line.trim_end().chars() {
+            match ch {
+                'O' | '*' => any_alive = true,
+                '.' => {}
+                _ => return false,
+            }
+        }
+    }
+    any_alive
+}
+
+pub fn parse_plaintext(text: &str) -> Result<Pattern, String>
+{
+    let mut name = None;
+    let mut author = None;
+    let mut comments = Vec::new();
+    let mut cells: Vec<Vec<u8>> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        if let Some(comment) = line.strip_prefix('!') {
+            if let Some(n) = comment.strip_prefix("Name:") {
+                name = Some(n.trim().to_string());
+            } else if let Some(a) = comment.strip_prefix("Author:") {
+                author = Some(a.trim().to_string());
+            } else {
+                comments.push(comment.trim().to_string());
+            }
+            continue;
+        }
+        let mut row = Vec::new();
+        for (col, ch) in line.trim_end().chars().enumerate() {
+            row.push(match ch {
+                'O' | '*' => 1,
+                '.' => 0,
+                other => {
+                    return Err(format!(
+                        "line {}, column {}: unexpected character '{}'",
+                        i + 1,
+                        col + 1,
+                        other
+                    ))
+                }
+            });
+        }
+        cells.push(row);
+    }
+
+    let width = cells.iter().map(|r| r.len()).max().unwrap_or(0);
+    for row in &mut cells {
+        row.resize(width, 0);
+    }
+    Ok(Pattern {
+        name,
+        author,
+        comments,
+        ..Pattern::from_cells(cells)
+    })
+}
+
+pub fn to_plaintext(pattern: &Pattern) -> String
+{
+    let mut out = String::new();
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("!Name: {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("!Author: {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("!{}\n", comment));
+    }
+    for row in &pattern.cells {
+        let end = row.iter().rposition(|&c| c != 0).map_or(0, |p| p + 1);
+        out.extend(row[..end].iter().map(|&c| if c != 0 { 'O' } else { '.' }));
+        out.push('\n');
+    }
+    out
+}
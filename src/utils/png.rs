@@ -0,0 +1,387 @@
+//! Minimal PNG encoder: 8-bit RGB, no filtering, and a deflate stream with
+//! fixed Huffman codes whose only matches are runs along a row and repeats
+//! of the row above. Cell images are mostly flat colour, so this compresses
+//! well without pulling in a dependency.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    str::FromStr,
+};
+
+use crate::utils::palette::Palette;
+
+/// Rectangle of cells, in grid coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region
+{
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region
+{
+    /// The part of this region that lies on a `width`x`height` grid; an
+    /// error if that is nothing, as no image format takes 0 pixels.
+    pub fn clamp(self, width: usize, height: usize) -> io::Result<Region>
+    {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        let region = Region {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "region {},{},{},{} has no cells on the {}x{} grid",
+                    self.x, self.y, self.width, self.height, width, height
+                ),
+            ));
+        }
+        Ok(region)
+    }
+}
+
+/// Cell states of `region` of a flat grid, one per pixel, with each cell
+/// blown up to a `scale`x`scale` block.
+pub fn rasterise(
+    cells: &[u8],
+    grid_width: usize,
+    region: Region,
+    scale: usize,
+) -> Vec<u8>
+{
+    let Region {
+        x,
+        y,
+        width,
+        height,
+    } = region;
+    let mut pixels = Vec::with_capacity(width * height * scale * scale);
+    for row in y..y + height {
+        let start = row * grid_width + x;
+        let line: Vec<u8> = cells[start..start + width]
+            .iter()
+            .flat_map(|&c| std::iter::repeat_n(c, scale))
+            .collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&line);
+        }
+    }
+    pixels
+}
+
+/// Parses `x,y,width,height`.
+impl FromStr for Region
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let fields = s
+            .split(',')
+            .map(|f| f.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let [x, y, width, height] = fields[..] else {
+            return Err("expected 'x,y,width,height'".to_string());
+        };
+        Ok(Region {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PngOptions
+{
+    /// Part of the grid to draw; the whole grid when `None`.
+    pub region: Option<Region>,
+    /// Side of a cell in pixels.
+    pub cell_size: usize,
+    /// Colour of 1-pixel lines drawn around every cell, if any.
+    pub grid_lines: Option<[u8; 3]>,
+    pub palette: Palette,
+}
+
+impl Default for PngOptions
+{
+    fn default() -> Self
+    {
+        PngOptions {
+            region: None,
+            cell_size: 1,
+            grid_lines: None,
+            palette: Palette::default(),
+        }
+    }
+}
+
+/// Renders `cells` (states as in the flat layout) to PNG bytes.
+pub fn encode_png(
+    cells: &[Vec<u8>],
+    options: &PngOptions,
+) -> io::Result<Vec<u8>>
+{
+    let grid_h = cells.len();
+    let grid_w = cells.first().map_or(0, |r| r.len());
+    let Region {
+        x: x0,
+        y: y0,
+        width: cols,
+        height: rows,
+    } = options
+        .region
+        .unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: grid_w,
+            height: grid_h,
+        })
+        .clamp(grid_w, grid_h)?;
+
+    let cell = options.cell_size.max(1);
+    let line = options.grid_lines.is_some() as usize;
+    let pitch = cell + line;
+    let side = |cells: usize| {
+        cells
+            .checked_mul(pitch)
+            .and_then(|n| n.checked_add(line))
+            .filter(|&n| n <= i32::MAX as usize)
+    };
+    let (Some(width), Some(height)) = (side(cols), side(rows)) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "PNG images are limited to 2147483647 pixels a side",
+        ));
+    };
+
+    // Each scanline starts with filter type 0.
+    let stride = 1 + width * 3;
+    let mut raw = Vec::with_capacity(stride * height);
+    for py in 0..height {
+        raw.push(0);
+        let on_row_line = line == 1 && py % pitch == 0;
+        for px in 0..width {
+            let on_line = line == 1 && (on_row_line || px % pitch == 0);
+            let colour = match options.grid_lines {
+                Some(colour) if on_line => colour,
+                _ => {
+                    let state = cells[y0 + (py - line) / pitch]
+                        [x0 + (px - line) / pitch];
+                    options.palette.colour(state)
+                }
+            };
+            raw.extend_from_slice(&colour);
+        }
+    }
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib(&raw, stride));
+    write_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+pub fn write_png(
+    path: &str,
+    cells: &[Vec<u8>],
+    options: &PngOptions,
+) -> io::Result<()>
+{
+    let png = encode_png(cells, options)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(&png)?;
+    out.flush()
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8])
+{
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32
+{
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32
+{
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Packs bit fields least significant first, as deflate and GIF's LZW
+/// both expect.
+pub(crate) struct BitWriter
+{
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter
+{
+    pub(crate) fn new(out: Vec<u8>) -> BitWriter
+    {
+        BitWriter {
+            out,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// Appends the low `n` bits of `value`, least significant first.
+    pub(crate) fn put(&mut self, value: u32, n: u32)
+    {
+        self.bits |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Appends a Huffman code, which deflate stores most significant first.
+    fn put_code(&mut self, code: u32, n: u32)
+    {
+        self.put(code.reverse_bits() >> (32 - n), n);
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8>
+    {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5,
+    5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+    11, 11, 12, 12, 13, 13,
+];
+const MAX_MATCH: usize = 258;
+const MAX_DISTANCE: usize = 32768;
+
+fn put_symbol(w: &mut BitWriter, symbol: u16)
+{
+    match symbol {
+        0..=143 => w.put_code(0x30 + symbol as u32, 8),
+        144..=255 => w.put_code(0x190 + (symbol - 144) as u32, 9),
+        256..=279 => w.put_code((symbol - 256) as u32, 7),
+        _ => w.put_code(0xC0 + (symbol - 280) as u32, 8),
+    }
+}
+
+fn put_match(w: &mut BitWriter, length: usize, distance: usize)
+{
+    let l = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= length)
+        .unwrap();
+    put_symbol(w, 257 + l as u16);
+    w.put(
+        (length - LENGTH_BASE[l] as usize) as u32,
+        LENGTH_EXTRA[l] as u32,
+    );
+
+    let d = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= distance)
+        .unwrap();
+    w.put_code(d as u32, 5);
+    w.put(
+        (distance - DIST_BASE[d] as usize) as u32,
+        DIST_EXTRA[d] as u32,
+    );
+}
+
+/// zlib stream of `data` as a single fixed-Huffman block, trying matches
+/// at distance 3 (the previous pixel) and `stride` (the row above).
+fn zlib(data: &[u8], stride: usize) -> Vec<u8>
+{
+    let mut w = BitWriter::new(vec![0x78, 0x01]);
+    w.put(1, 1);
+    w.put(1, 2);
+
+    let match_len = |i: usize, distance: usize| {
+        if distance > i || distance > MAX_DISTANCE {
+            return 0;
+        }
+        data[i..]
+            .iter()
+            .zip(&data[i - distance..])
+            .take(MAX_MATCH)
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (length, distance) = [3, stride]
+            .into_iter()
+            .map(|d| (match_len(i, d), d))
+            .max_by_key(|&(len, _)| len)
+            .unwrap();
+        if length >= 3 {
+            put_match(&mut w, length, distance);
+            i += length;
+        } else {
+            put_symbol(&mut w, data[i] as u16);
+            i += 1;
+        }
+    }
+    put_symbol(&mut w, 256);
+
+    let mut out = w.finish();
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
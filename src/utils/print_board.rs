@@ -72,4 +72,4 @@ pub fn render_grid<F>(
         sleep(tick);
     }
 }
- */
\ No newline at end of file
+ */
@@ -0,0 +1,318 @@
+use crate::utils::{error::MAX_SIDE, pattern::Pattern};
+
+const LINE_WIDTH: usize = 70;
+
+/// True if the text looks like RLE: optional `#` lines, then an `x = `
+/// header.
+pub fn is_rle(text: &str) -> bool
+{
+    text.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'))
+        .is_some_and(|l| {
+            l.strip_prefix('x')
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+}
+
+/// Parses a side length from the header, no larger than MAX_SIDE.
+fn parse_side(key: &str, value: &str) -> Result<usize, String>
+{
+    let side: usize = value.parse().map_err(|e| format!("{}: {}", key, e))?;
+    if side > MAX_SIDE {
+        return Err(format!(
+            "{} = {} is over the {} limit",
+            key, side, MAX_SIDE
+        ));
+    }
+    Ok(side)
+}
+
+/// The rule takes the rest of the line, as rules such as `B3/S23:T10,10`
+/// have commas of their own.
+fn parse_header(line: &str, pattern: &mut Pattern) -> Result<(), String>
+{
+    let (sizes, rule) = match line.find("rule") {
+        Some(at) => (line[..at].trim_end().trim_end_matches(','), &line[at..]),
+        None => (line, ""),
+    };
+    for field in sizes.split(',') {
+        let Some((key, value)) = field.split_once('=') else {
+            return Err(format!("malformed header field '{}'", field.trim()));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "x" => pattern.width = parse_side("x", value)?,
+            "y" => pattern.height = parse_side("y", value)?,
+            _ => {}
+        }
+    }
+    if !rule.is_empty() {
+        let Some((_, value)) = rule.split_once('=') else {
+            return Err(format!("malformed header field '{}'", rule));
+        };
+        pattern.rule = Some(value.trim().to_string());
+    }
+    Ok(())
+}
+
+pub fn parse_rle(text: &str) -> Result<Pattern, String>
+{
+    let mut pattern = Pattern::default();
+    let mut lines = text.lines().enumerate();
+
+    for (i, line) in lines.by_ref() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix('#') {
+            let mut chars = rest.chars();
+            let tag = chars.next();
+            let body = chars.as_str().trim().to_string();
+            match tag {
+                Some('N') => pattern.name = Some(body),
+                Some('O') => pattern.author = Some(body),
+                Some('C') | Some('c') => pattern.comments.push(body),
+                _ => {}
+            }
+        } else if line.starts_with('x') {
+            parse_header(line, &mut pattern)
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            break;
+        } else if !line.is_empty() {
+            return Err(format!("line {}: expected 'x = ' header", i + 1));
+        }
+    }
+
+    let mut rows: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut count: usize = 0;
+    let mut prefix: Option<u8> = None;
+
+    'data: for (i, line) in lines {
+        for (col, ch) in line.chars().enumerate() {
+            let at = || format!("line {}, column {}", i + 1, col + 1);
+            if prefix.is_some() && !ch.is_ascii_uppercase() {
+                return Err(format!("{}: dangling state prefix", at()));
+            }
+            let state = match ch {
+                '0'..='9' => {
+                    count = count * 10 + ch.to_digit(10).unwrap() as usize;
+                    if count > MAX_SIDE {
+                        return Err(format!(
+                            "{}: run is over the {} limit",
+                            at(),
+                            MAX_SIDE
+                        ));
+                    }
+                    continue;
+                }
+                'p'..='y' => {
+                    prefix = Some(ch as u8 - b'p' + 1);
+                    continue;
+                }
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => {
+                    let base = prefix.take().unwrap_or(0) as usize * 24;
+                    let state = base + (ch as u8 - b'A') as usize + 1;
+                    u8::try_from(state)
+                        .map_err(|_| format!("{}: state out of range", at()))?
+                }
+                '$' => {
+                    if rows.len() + count > MAX_SIDE {
+                        return Err(format!(
+                            "{}: more than {} rows",
+                            at(),
+                            MAX_SIDE
+                        ));
+                    }
+                    for _ in 0..count.max(1) {
+                        rows.push(Vec::new());
+                    }
+                    count = 0;
+                    continue;
+                }
+                '!' => break 'data,
+                c if c.is_whitespace() => continue,
+                other => {
+                    return Err(format!(
+                        "{}: unexpected character '{}'",
+                        at(),
+                        other
+                    ))
+                }
+            };
+            let row = rows.last_mut().unwrap();
+            if row.len() + count.max(1) > MAX_SIDE {
+                return Err(format!(
+                    "{}: row is wider than {}",
+                    at(),
+                    MAX_SIDE
+                ));
+            }
+            row.extend(std::iter::repeat_n(state, count.max(1)));
+            count = 0;
+        }
+    }
+
+    while rows.last().is_some_and(|r| r.is_empty()) && rows.len() > 1 {
+        rows.pop();
+    }
+    pattern.width = pattern
+        .width
+        .max(rows.iter().map(|r| r.len()).max().unwrap_or(0));
+    pattern.height = pattern.height.max(rows.len());
+    rows.resize(pattern.height, Vec::new());
+    for row in &mut rows {
+        row.resize(pattern.width, 0);
+    }
+    pattern.cells = rows;
+    Ok(pattern)
+}
+
+fn state_token(state: u8, multi_state: bool) -> String
+{
+    match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (s, true) => {
+            let s = s - 1;
+            let letter = char::from(b'A' + s % 24);
+            match s / 24 {
+                0 => letter.to_string(),
+                p => format!("{}{}", char::from(b'p' + p - 1), letter),
+            }
+        }
+    }
+}
+
+/// Encodes a pattern as RLE, choosing multi-state letters when any cell is
+/// above 1.
+pub fn to_rle(pattern: &Pattern) -> String
+{
+    let cells = &pattern.cells;
+    let multi_state = cells.iter().flatten().any(|&c| c > 1);
+
+    let mut out = String::new();
+    if let Some(name) = &pattern.name {
+        out.push_str(&format!("#N {}\n", name));
+    }
+    if let Some(author) = &pattern.author {
+        out.push_str(&format!("#O {}\n", author));
+    }
+    for comment in &pattern.comments {
+        out.push_str(&format!("#C {}\n", comment));
+    }
+    out.push_str(&format!("x = {}, y = {}", pattern.width, pattern.height));
+    if let Some(rule) = &pattern.rule {
+        out.push_str(&format!(", rule = {}", rule));
+    }
+    out.push('\n');
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut pending_rows = 0usize;
+    for row in cells {
+        let end = row.iter().rposition(|&c| c != 0).map_or(0, |p| p + 1);
+        if end == 0 {
+            pending_rows += 1;
+            continue;
+        }
+        if !tokens.is_empty() || pending_rows > 0 {
+            let rows = pending_rows + usize::from(!tokens.is_empty());
+            tokens.push(match rows {
+                1 => "$".to_string(),
+                n => format!("{}$", n),
+            });
+        }
+        pending_rows = 0;
+
+        let mut x = 0;
+        while x < end {
+            let state = row[x];
+            let run = row[x..end].iter().take_while(|&&c| c == state).count();
+            let token = state_token(state, multi_state);
+            tokens.push(match run {
+                1 => token,
+                n => format!("{}{}", n, token),
+            });
+            x += run;
+        }
+    }
+    tokens.push("!".to_string());
+
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len + token.len() > LINE_WIDTH {
+            out.push('\n');
+            line_len = 0;
+        }
+        line_len += token.len();
+        out.push_str(&token);
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn round_trip()
+    {
+        let pattern = Pattern {
+            name: Some("glider".to_string()),
+            author: Some("Richard K. Guy".to_string()),
+            comments: vec!["found in 1969".to_string()],
+            rule: Some("B3/S23".to_string()),
+            ..Pattern::from_cells(vec![
+                vec![0, 1, 0],
+                vec![0, 0, 1],
+                vec![1, 1, 1],
+            ])
+        };
+        let text = to_rle(&pattern);
+        assert!(text.ends_with("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"));
+        assert_eq!(parse_rle(&text), Ok(pattern));
+    }
+
+    #[test]
+    fn multi_state_letters()
+    {
+        let pattern = Pattern::from_cells(vec![vec![1, 2, 24, 25, 48, 255]]);
+        let text = to_rle(&pattern);
+        assert!(text.ends_with("\nABXpApXyO!\n"));
+        assert_eq!(parse_rle(&text).unwrap().cells, pattern.cells);
+    }
+
+    #[test]
+    fn long_rows_wrap_between_runs()
+    {
+        let row = (0..300).map(|x| u8::from(x % 3 == 0)).collect();
+        let pattern = Pattern::from_cells(vec![row]);
+        let text = to_rle(&pattern);
+        assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(text.lines().count() > 3);
+        assert_eq!(parse_rle(&text).unwrap().cells, pattern.cells);
+    }
+
+    #[test]
+    fn run_counts_carry_across_lines()
+    {
+        let pattern = parse_rle("x = 0, y = 0\n1\n2o$\n3b\no!\n").unwrap();
+        assert_eq!(pattern.cells[0], vec![1; 12]);
+        assert_eq!(pattern.cells[1], [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected()
+    {
+        assert!(parse_rle("bo$2bo$3o!").is_err());
+        assert!(parse_rle("x = 3, y\nbo!").is_err());
+        assert!(parse_rle("x = 99999, y = 1\no!").is_err());
+        assert!(parse_rle("x = 1, y = 1\npo!").is_err());
+        assert!(parse_rle("x = 1, y = 1\nyX!").is_err());
+        assert!(parse_rle("x = 1, y = 1\nz!").is_err());
+        assert!(parse_rle("x = 1, y = 1\n99999o!").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fmt, str::FromStr};
+
+/// Outer-totalistic Life-like rule in B/S notation, e.g. `B3/S23`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule
+{
+    birth: u16,
+    survival: u16,
+}
+
+impl Rule
+{
+    pub fn conway() -> Rule
+    {
+        Rule {
+            birth: 1 << 3,
+            survival: (1 << 2) | (1 << 3),
+        }
+    }
+
+    pub fn born(&self, count: usize) -> bool
+    {
+        count < 16 && (self.birth >> count) & 1 == 1
+    }
+
+    pub fn survives(&self, count: usize) -> bool
+    {
+        count < 16 && (self.survival >> count) & 1 == 1
+    }
+
+    pub fn next(&self, cell: u8, count: u8) -> u8
+    {
+        let alive = if cell != 0 {
+            self.survives(count as usize)
+        } else {
+            self.born(count as usize)
+        };
+        alive as u8
+    }
+
+    /// State of an infinite uniform background after one generation; a
+    /// rule with B0 turns a dead background alive.
+    pub fn background_next(&self, background: u8) -> u8
+    {
+        self.next(background, 8 * background)
+    }
+
+    /// The older survival/birth notation, e.g. `23/3`, used by Life 1.05.
+    pub fn to_sb_string(&self) -> String
+    {
+        format!("{}/{}", digits(self.survival), digits(self.birth))
+    }
+
+    /// Next-state table indexed by `cell * 9 + count`, for the flat engines.
+    pub fn table(&self) -> [u8; 18]
+    {
+        let mut table = [0u8; 18];
+        for (i, slot) in table.iter_mut().enumerate() {
+            *slot = self.next((i / 9) as u8, (i % 9) as u8);
+        }
+        table
+    }
+}
+
+fn digits(mask: u16) -> String
+{
+    (0..=8)
+        .filter(|&n| (mask >> n) & 1 == 1)
+        .map(|n| char::from(b'0' + n as u8))
+        .collect()
+}
+
+impl fmt::Display for Rule
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "B{}/S{}", digits(self.birth), digits(self.survival))
+    }
+}
+
+impl FromStr for Rule
+{
+    type Err = String;
+
+    /// Accepts `B3/S23` (any case) and the older `23/3` survival/birth form.
+    fn from_str(s: &str) -> Result<Rule, String>
+    {
+        let parse_digits = |part: &str| -> Result<u16, String> {
+            let mut mask = 0u16;
+            for ch in part.chars() {
+                match ch.to_digit(10) {
+                    Some(d) if d <= 8 => mask |= 1 << d,
+                    _ => return Err(format!("invalid rule digit '{}'", ch)),
+                }
+            }
+            Ok(mask)
+        };
+
+        let s = s.trim();
+        let (first, second) = s
+            .split_once('/')
+            .ok_or_else(|| format!("rule '{}' has no '/'", s))?;
+
+        let tagged = |part: &str, tag: char| {
+            part.strip_prefix(tag)
+                .or_else(|| part.strip_prefix(tag.to_ascii_lowercase()))
+                .map(str::to_string)
+        };
+
+        let (birth, survival) = match (
+            tagged(first, 'B'),
+            tagged(second, 'S'),
+            tagged(first, 'S'),
+            tagged(second, 'B'),
+        ) {
+            (Some(b), Some(s), _, _) => (b, s),
+            (_, _, Some(s), Some(b)) => (b, s),
+            _ => (second.to_string(), first.to_string()),
+        };
+
+        Ok(Rule {
+            birth: parse_digits(&birth)?,
+            survival: parse_digits(&survival)?,
+        })
+    }
+}
+
+impl Default for Rule
+{
+    fn default() -> Rule
+    {
+        Rule::conway()
+    }
+}
+
+/// A rule per region of the grid; `regions` holds one index into `rules`
+/// for every cell of the flat layout.
+pub struct RuleMap
+{
+    pub rules: Vec<Rule>,
+    pub regions: Vec<u8>,
+}
+
+impl RuleMap
+{
+    pub fn uniform(rule: Rule, area: usize) -> RuleMap
+    {
+        RuleMap {
+            rules: vec![rule],
+            regions: vec![0; area],
+        }
+    }
+
+    pub fn tables(&self) -> Vec<[u8; 18]>
+    {
+        self.rules.iter().map(Rule::table).collect()
+    }
+}
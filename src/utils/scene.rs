@@ -0,0 +1,280 @@
+//! Scene files: starting grids composed from several two-state patterns,
+//! each placed, turned, flipped and advanced on a shared canvas.
+
+use std::{collections::HashSet, path::Path, str::FromStr};
+
+use crate::utils::{
+    apgcode::step,
+    error::LoadError,
+    rule::Rule,
+    transform::{crop, reflect, rotate, Axis},
+};
+
+/// One pattern placed on a scene's canvas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Placement
+{
+    /// File, relative to the scene file, or a library name or apgcode.
+    pub source: String,
+    /// Canvas position of the top-left corner of the pattern's bounding
+    /// box, before any phase advance moves it; blank margins in the
+    /// source do not count.
+    pub x: i64,
+    pub y: i64,
+    /// Clockwise quarter turns, applied after the reflection.
+    pub quarter_turns: usize,
+    pub flip: Option<Axis>,
+    /// Generations to run the pattern for before placing it.
+    pub phase: usize,
+    /// Line of the `[[pattern]]` header, for error messages.
+    pub line: usize,
+}
+
+/// Several patterns composed into one starting grid.
+///
+/// Scene files are a small subset of TOML: `width`, `height` and an
+/// optional `rule` for the canvas, then one `[[pattern]]` table per
+/// placement with `source`, `x`, `y`, `rotate` (0, 90, 180 or 270),
+/// `flip` (`"x"` or `"y"`) and `phase`; `#` starts a comment.
+///
+/// ```toml
+/// width = 128
+/// height = 64
+///
+/// [[pattern]]
+/// source = "glider"
+/// x = 10
+/// y = 10
+///
+/// [[pattern]]
+/// source = "glider"
+/// x = 40
+/// y = 10
+/// flip = "x"
+/// phase = 2
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scene
+{
+    pub width: usize,
+    pub height: usize,
+    pub rule: Option<Rule>,
+    pub placements: Vec<Placement>,
+}
+
+/// True for paths with a `.scene` or `.toml` extension.
+pub fn is_scene_path(path: &str) -> bool
+{
+    path.rsplit_once('.').is_some_and(|(_, ext)| {
+        ext.eq_ignore_ascii_case("scene") || ext.eq_ignore_ascii_case("toml")
+    })
+}
+
+enum Value
+{
+    Int(i64),
+    Str(String),
+}
+
+/// Drops a `#` comment, unless it is inside a string.
+fn strip_comment(line: &str) -> &str
+{
+    let mut quoted = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String>
+{
+    if let Some(s) = text.strip_prefix('"') {
+        let s = s
+            .strip_suffix('"')
+            .ok_or_else(|| format!("unterminated string {}", text))?;
+        return Ok(Value::Str(s.to_string()));
+    }
+    text.parse::<i64>().map(Value::Int).map_err(|_| {
+        format!("expected a number or a \"string\", found '{}'", text)
+    })
+}
+
+impl FromStr for Scene
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Scene, String>
+    {
+        let mut width = None;
+        let mut height = None;
+        let mut rule = None;
+        let mut placements: Vec<Placement> = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let at = |e: String| format!("line {}: {}", i + 1, e);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[pattern]]" {
+                placements.push(Placement {
+                    source: String::new(),
+                    x: 0,
+                    y: 0,
+                    quarter_turns: 0,
+                    flip: None,
+                    phase: 0,
+                    line: i + 1,
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(at(format!(
+                    "expected 'key = value', found '{}'",
+                    line
+                )));
+            };
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(at)?;
+            let count = |v: &Value| match v {
+                Value::Int(n) if *n >= 0 => Ok(*n as usize),
+                _ => Err(at(format!("'{}' must be a whole number", key))),
+            };
+            let text = |v: Value| match v {
+                Value::Str(s) => Ok(s),
+                Value::Int(_) => Err(at(format!("'{}' must be a string", key))),
+            };
+
+            let Some(placement) = placements.last_mut() else {
+                match key {
+                    "width" => width = Some(count(&value)?),
+                    "height" => height = Some(count(&value)?),
+                    "rule" => {
+                        rule = Some(text(value)?.parse::<Rule>().map_err(at)?)
+                    }
+                    _ => {
+                        return Err(at(format!("unknown canvas key '{}'", key)))
+                    }
+                }
+                continue;
+            };
+            match (key, value) {
+                ("source", value) => placement.source = text(value)?,
+                ("x", Value::Int(n)) => placement.x = n,
+                ("y", Value::Int(n)) => placement.y = n,
+                ("rotate", Value::Int(n)) if n.rem_euclid(90) == 0 => {
+                    placement.quarter_turns = (n.rem_euclid(360) / 90) as usize
+                }
+                ("rotate", _) => {
+                    return Err(at(
+                        "'rotate' must be a multiple of 90".to_string()
+                    ))
+                }
+                ("flip", value) => {
+                    placement.flip = match text(value)?.as_str() {
+                        "x" => Some(Axis::X),
+                        "y" => Some(Axis::Y),
+                        "none" => None,
+                        other => {
+                            return Err(at(format!(
+                                "'flip' must be \"x\" or \"y\", found '{}'",
+                                other
+                            )))
+                        }
+                    }
+                }
+                ("phase", value) => placement.phase = count(&value)?,
+                ("x" | "y", _) => {
+                    return Err(at(format!("'{}' must be a number", key)))
+                }
+                (key, _) => {
+                    return Err(at(format!("unknown pattern key '{}'", key)))
+                }
+            }
+        }
+
+        if let Some(p) = placements.iter().find(|p| p.source.is_empty()) {
+            return Err(format!("line {}: pattern has no 'source'", p.line));
+        }
+        Ok(Scene {
+            width: width.ok_or("missing 'width'")?,
+            height: height.ok_or("missing 'height'")?,
+            rule,
+            placements,
+        })
+    }
+}
+
+impl Scene
+{
+    /// Builds the starting grid of the scene at `path`. `load` reads the
+    /// cells of a source; file sources are looked up next to the scene
+    /// file first. Patterns may overlap, but must fit on the canvas.
+    pub fn compose(
+        &self,
+        path: &str,
+        mut load: impl FnMut(&str) -> Result<Vec<Vec<u8>>, LoadError>,
+    ) -> Result<Vec<Vec<u8>>, LoadError>
+    {
+        let rule = self.rule.unwrap_or_else(Rule::conway);
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut canvas = vec![vec![0u8; self.width]; self.height];
+
+        for placement in &self.placements {
+            let at = |message: &str| {
+                LoadError::invalid(
+                    path,
+                    format!("line {}: {}", placement.line, message),
+                )
+            };
+            let local = dir.join(&placement.source);
+            let source = match local.exists() {
+                true => local.to_string_lossy().into_owned(),
+                false => placement.source.clone(),
+            };
+
+            let mut cells = load(&source)?;
+            if cells.iter().flatten().any(|&c| c > 1) {
+                return Err(at(&format!(
+                    "'{}' has more than two states",
+                    placement.source
+                )));
+            }
+            if let Some(axis) = placement.flip {
+                cells = reflect(&cells, axis);
+            }
+            cells = crop(&rotate(&cells, placement.quarter_turns));
+
+            let mut live: HashSet<(i64, i64)> = HashSet::new();
+            for (y, row) in cells.iter().enumerate() {
+                for (x, _) in row.iter().enumerate().filter(|(_, &c)| c != 0) {
+                    live.insert((x as i64, y as i64));
+                }
+            }
+            if placement.phase > 0 && rule.born(0) {
+                return Err(at("phases cannot be advanced under B0 rules"));
+            }
+            for _ in 0..placement.phase {
+                live = step(&live, rule);
+            }
+
+            for (x, y) in live {
+                let (x, y) = (x + placement.x, y + placement.y);
+                let inside = (0..self.width as i64).contains(&x)
+                    && (0..self.height as i64).contains(&y);
+                if !inside {
+                    return Err(at(&format!(
+                        "'{}' does not fit on the {}x{} canvas",
+                        placement.source, self.width, self.height
+                    )));
+                }
+                canvas[y as usize][x as usize] = 1;
+            }
+        }
+        Ok(canvas)
+    }
+}
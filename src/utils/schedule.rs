@@ -0,0 +1,103 @@
+use std::{fs, str::FromStr};
+
+use crate::utils::{error::LoadError, rule::Rule};
+
+/// Sequence of rules, each held for a number of generations.
+///
+/// Schedule files list one `<rule> <generations>` pair per line, e.g.
+/// `B3/S23 100`; `#` starts a comment. A final `repeat` line cycles the
+/// schedule, otherwise the last rule stays in force once it runs out.
+#[derive(Clone, Debug)]
+pub struct Schedule
+{
+    steps: Vec<(Rule, usize)>,
+    repeat: bool,
+}
+
+impl Schedule
+{
+    pub fn constant(rule: Rule) -> Schedule
+    {
+        Schedule {
+            steps: vec![(rule, 1)],
+            repeat: true,
+        }
+    }
+
+    pub fn new(steps: Vec<(Rule, usize)>, repeat: bool) -> Schedule
+    {
+        assert!(
+            steps.iter().any(|&(_, n)| n > 0),
+            "schedule must cover at least one generation"
+        );
+        Schedule { steps, repeat }
+    }
+
+    /// Rule used to compute generation `generation + 1` from `generation`.
+    pub fn rule_at(&self, generation: usize) -> Rule
+    {
+        let period: usize = self.steps.iter().map(|&(_, n)| n).sum();
+        let mut t = if self.repeat {
+            generation % period
+        } else {
+            generation
+        };
+        for &(rule, n) in &self.steps {
+            if t < n {
+                return rule;
+            }
+            t -= n;
+        }
+        self.steps.last().map(|&(rule, _)| rule).unwrap_or_default()
+    }
+}
+
+impl FromStr for Schedule
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String>
+    {
+        let mut steps = Vec::new();
+        let mut repeat = false;
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if repeat {
+                return Err(format!("line {}: entry after 'repeat'", i + 1));
+            }
+            if line.eq_ignore_ascii_case("repeat") {
+                repeat = true;
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (Some(rule), Some(count), None) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(format!(
+                    "line {}: expected '<rule> <generations>'",
+                    i + 1
+                ));
+            };
+            let rule = rule
+                .parse::<Rule>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            let count = count
+                .parse::<usize>()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            steps.push((rule, count));
+        }
+        if !steps.iter().any(|&(_, n)| n > 0) {
+            return Err("schedule covers no generations".to_string());
+        }
+        Ok(Schedule { steps, repeat })
+    }
+}
+
+pub fn load_schedule(path: &str) -> Result<Schedule, LoadError>
+{
+    let text = fs::read_to_string(path).map_err(|e| LoadError::io(path, e))?;
+    text.parse().map_err(|e| LoadError::invalid(path, e))
+}
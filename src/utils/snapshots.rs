@@ -0,0 +1,114 @@
+//! Periodic checkpoints of a long run, written into a directory as
+//! `generation-<n>.ckpt` with only the most recent few kept.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::utils::checkpoint::{write_checkpoint, Checkpoint};
+
+const PREFIX: &str = "generation-";
+const EXTENSION: &str = ".ckpt";
+
+#[derive(Clone, Debug)]
+pub struct SnapshotOptions
+{
+    /// Save whenever the generation number is a multiple of this.
+    pub every: Option<usize>,
+    /// Save when this long has passed since the last snapshot.
+    pub interval: Option<Duration>,
+    /// Number of snapshots left in the directory; 0 keeps them all.
+    pub keep: usize,
+}
+
+impl Default for SnapshotOptions
+{
+    fn default() -> Self
+    {
+        SnapshotOptions {
+            every: None,
+            interval: None,
+            keep: 5,
+        }
+    }
+}
+
+pub struct Snapshots
+{
+    dir: PathBuf,
+    options: SnapshotOptions,
+    last: Instant,
+}
+
+/// Generation number of a snapshot file written by `Snapshots`.
+fn snapshot_generation(path: &Path) -> Option<usize>
+{
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(PREFIX)?
+        .strip_suffix(EXTENSION)?
+        .parse()
+        .ok()
+}
+
+impl Snapshots
+{
+    /// Saves into `dir`, creating it if needed.
+    pub fn new(dir: &str, options: SnapshotOptions) -> io::Result<Snapshots>
+    {
+        fs::create_dir_all(dir)?;
+        Ok(Snapshots {
+            dir: PathBuf::from(dir),
+            options,
+            last: Instant::now(),
+        })
+    }
+
+    /// True if `generation` should be saved under the options.
+    pub fn due(&self, generation: usize) -> bool
+    {
+        let every = self
+            .options
+            .every
+            .is_some_and(|n| n > 0 && generation.is_multiple_of(n));
+        let interval = self
+            .options
+            .interval
+            .is_some_and(|t| self.last.elapsed() >= t);
+        every || interval
+    }
+
+    /// Writes `checkpoint`, then deletes the oldest snapshots beyond the
+    /// number to keep. Returns the path written.
+    pub fn save(&mut self, checkpoint: &Checkpoint) -> io::Result<PathBuf>
+    {
+        let name =
+            format!("{}{:012}{}", PREFIX, checkpoint.generation, EXTENSION);
+        let path = self.dir.join(name);
+        write_checkpoint(&path, checkpoint)?;
+        self.last = Instant::now();
+        self.rotate()?;
+        Ok(path)
+    }
+
+    fn rotate(&self) -> io::Result<()>
+    {
+        if self.options.keep == 0 {
+            return Ok(());
+        }
+        let mut saved: Vec<(usize, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                Some((snapshot_generation(&path)?, path))
+            })
+            .collect();
+        saved.sort_unstable();
+        let excess = saved.len().saturating_sub(self.options.keep);
+        for (_, path) in &saved[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+//! SVG rendering for figures. Horizontal runs of same-state cells become a
+//! single `<rect>`, and each state gets a CSS class so the colours can be
+//! restyled after export.
+
+use std::{fmt::Write as _, fs, io};
+
+use crate::utils::{objects::find_objects, palette::Palette, png::Region};
+
+/// Colour of the boxes drawn around detected objects.
+const HIGHLIGHT: &str = "#ff3b30";
+
+#[derive(Clone, Debug)]
+pub struct SvgOptions
+{
+    /// Part of the grid to draw; the whole grid when `None`.
+    pub region: Option<Region>,
+    /// Side of a cell in SVG user units.
+    pub cell_size: usize,
+    /// Colour of lines drawn between cells, if any.
+    pub grid_lines: Option<[u8; 3]>,
+    /// Number every `label_every`-th row and column along the edges.
+    pub labels: bool,
+    pub label_every: usize,
+    /// Outline the bounding box of every object in the region.
+    pub highlight_objects: bool,
+    pub palette: Palette,
+}
+
+impl Default for SvgOptions
+{
+    fn default() -> Self
+    {
+        SvgOptions {
+            region: None,
+            cell_size: 10,
+            grid_lines: None,
+            labels: false,
+            label_every: 10,
+            highlight_objects: false,
+            palette: Palette::default(),
+        }
+    }
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String
+{
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Renders `cells` (states as in the flat layout) as an SVG document.
+pub fn to_svg(cells: &[Vec<u8>], options: &SvgOptions) -> io::Result<String>
+{
+    let grid_h = cells.len();
+    let grid_w = cells.first().map_or(0, |r| r.len());
+    let region = options
+        .region
+        .unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width: grid_w,
+            height: grid_h,
+        })
+        .clamp(grid_w, grid_h)?;
+    let cell = options.cell_size.max(1);
+    let margin = if options.labels { 3 * cell } else { 0 };
+    let width = region.width * cell;
+    let height = region.height * cell;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" \
+         height=\"{h}\" viewBox=\"{o} {o} {w} {h}\">",
+        w = width + margin,
+        h = height + margin,
+        o = -(margin as i64),
+    );
+
+    let mut states: Vec<u8> = cells[region.y..region.y + region.height]
+        .iter()
+        .flat_map(|row| &row[region.x..region.x + region.width])
+        .copied()
+        .filter(|&c| c != 0)
+        .collect();
+    states.sort_unstable();
+    states.dedup();
+    out.push_str("<style>\n");
+    for &state in &states {
+        let colour = hex(options.palette.colour(state));
+        let _ = writeln!(out, ".s{} {{ fill: {}; }}", state, colour);
+    }
+    out.push_str("</style>\n");
+    let _ = writeln!(
+        out,
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        width,
+        height,
+        hex(options.palette.colour(0))
+    );
+
+    for (dy, row) in
+        cells[region.y..region.y + region.height].iter().enumerate()
+    {
+        let row = &row[region.x..region.x + region.width];
+        let mut x = 0;
+        while x < row.len() {
+            let state = row[x];
+            let run = row[x..].iter().take_while(|&&c| c == state).count();
+            if state != 0 {
+                let _ = writeln!(
+                    out,
+                    "<rect class=\"s{}\" x=\"{}\" y=\"{}\" width=\"{}\" \
+                     height=\"{}\"/>",
+                    state,
+                    x * cell,
+                    dy * cell,
+                    run * cell,
+                    cell
+                );
+            }
+            x += run;
+        }
+    }
+
+    if let Some(colour) = options.grid_lines {
+        let mut path = String::new();
+        for i in 0..=region.width {
+            let _ = write!(path, "M{} 0V{}", i * cell, height);
+        }
+        for j in 0..=region.height {
+            let _ = write!(path, "M0 {}H{}", j * cell, width);
+        }
+        let _ = writeln!(
+            out,
+            "<path d=\"{}\" stroke=\"{}\" stroke-width=\"{}\" fill=\"none\"/>",
+            path,
+            hex(colour),
+            cell as f32 / 16.0
+        );
+    }
+
+    if options.labels {
+        let every = options.label_every.max(1);
+        let _ = writeln!(
+            out,
+            "<g font-family=\"monospace\" font-size=\"{}\" fill=\"#000000\">",
+            cell
+        );
+        for i in
+            (0..region.width).filter(|i| (region.x + i).is_multiple_of(every))
+        {
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                i * cell + cell / 2,
+                -(cell as i64) / 2,
+                region.x + i
+            );
+        }
+        for j in
+            (0..region.height).filter(|j| (region.y + j).is_multiple_of(every))
+        {
+            let _ = writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" \
+                 dominant-baseline=\"central\">{}</text>",
+                -(cell as i64) / 2,
+                j * cell + cell / 2,
+                region.y + j
+            );
+        }
+        out.push_str("</g>\n");
+    }
+
+    if options.highlight_objects {
+        let inside = |o: &Region| {
+            o.x >= region.x
+                && o.y >= region.y
+                && o.x + o.width <= region.x + region.width
+                && o.y + o.height <= region.y + region.height
+        };
+        for object in find_objects(cells).iter().filter(|o| inside(o)) {
+            let _ = writeln!(
+                out,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                 fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+                (object.x - region.x) * cell,
+                (object.y - region.y) * cell,
+                object.width * cell,
+                object.height * cell,
+                HIGHLIGHT,
+                cell as f32 / 5.0
+            );
+        }
+    }
+
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+pub fn write_svg(
+    path: &str,
+    cells: &[Vec<u8>],
+    options: &SvgOptions,
+) -> io::Result<()>
+{
+    fs::write(path, to_svg(cells, options)?)
+}
@@ -0,0 +1,349 @@
+//! Geometric transformations of cell grids, and of patterns with their
+//! metadata kept.
+
+use std::str::FromStr;
+
+use crate::utils::{error::MAX_SIDE, pattern::Pattern};
+
+/// Mirror axis for `reflect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis
+{
+    /// Mirror left to right.
+    X,
+    /// Mirror top to bottom.
+    Y,
+}
+
+/// Rotates `cells` clockwise by `quarter_turns` right angles.
+pub fn rotate<T: Copy>(cells: &[Vec<T>], quarter_turns: usize) -> Vec<Vec<T>>
+{
+    let mut out = cells.to_vec();
+    for _ in 0..quarter_turns % 4 {
+        let height = out.len();
+        let width = out.first().map_or(0, |r| r.len());
+        out = (0..width)
+            .map(|x| (0..height).rev().map(|y| out[y][x]).collect())
+            .collect();
+    }
+    out
+}
+
+pub fn reflect<T: Copy>(cells: &[Vec<T>], axis: Axis) -> Vec<Vec<T>>
+{
+    match axis {
+        Axis::X => cells
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect(),
+        Axis::Y => cells.iter().rev().cloned().collect(),
+    }
+}
+
+/// Moves the cells `dx` right and `dy` down within the same grid; cells
+/// pushed past an edge are lost.
+pub fn translate<T: Copy + Default>(
+    cells: &[Vec<T>],
+    dx: i64,
+    dy: i64,
+) -> Vec<Vec<T>>
+{
+    let height = cells.len() as i64;
+    let width = cells.first().map_or(0, |r| r.len()) as i64;
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    // Saturating, so that huge shifts land off the grid.
+                    let (sx, sy) = (x.saturating_sub(dx), y.saturating_sub(dy));
+                    if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                        cells[sy as usize][sx as usize]
+                    } else {
+                        T::default()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Trims dead rows and columns from the edges, down to the bounding box of
+/// the live cells; a grid with none becomes empty.
+pub fn crop<T: Copy + Default + PartialEq>(cells: &[Vec<T>]) -> Vec<Vec<T>>
+{
+    let live = |c: &T| *c != T::default();
+    let rows: Vec<usize> = (0..cells.len())
+        .filter(|&y| cells[y].iter().any(live))
+        .collect();
+    let (Some(&top), Some(&bottom)) = (rows.first(), rows.last()) else {
+        return Vec::new();
+    };
+    let left = cells.iter().filter_map(|r| r.iter().position(live)).min();
+    let right = cells.iter().filter_map(|r| r.iter().rposition(live)).max();
+    let (left, right) = (left.unwrap_or(0), right.unwrap_or(0));
+    cells[top..=bottom]
+        .iter()
+        .map(|row| row[left..=right].to_vec())
+        .collect()
+}
+
+/// Adds dead margins of the given widths around the grid.
+pub fn pad<T: Copy + Default>(
+    cells: &[Vec<T>],
+    top: usize,
+    right: usize,
+    bottom: usize,
+    left: usize,
+) -> Vec<Vec<T>>
+{
+    let width = cells.first().map_or(0, |r| r.len()) + left + right;
+    let dead_row = vec![T::default(); width];
+    let mut out = vec![dead_row.clone(); top];
+    for row in cells {
+        let mut padded = vec![T::default(); left];
+        padded.extend_from_slice(row);
+        padded.resize(width, T::default());
+        out.push(padded);
+    }
+    out.extend(std::iter::repeat_n(dead_row, bottom));
+    out
+}
+
+/// Repeats the grid `across` times side by side and `down` times top to
+/// bottom.
+pub fn tile<T: Copy>(
+    cells: &[Vec<T>],
+    across: usize,
+    down: usize,
+) -> Vec<Vec<T>>
+{
+    let rows: Vec<Vec<T>> =
+        cells.iter().map(|row| row.repeat(across)).collect();
+    (0..down).flat_map(|_| rows.iter().cloned()).collect()
+}
+
+/// Blows every cell up into a `factor`x`factor` block.
+pub fn scale<T: Copy>(cells: &[Vec<T>], factor: usize) -> Vec<Vec<T>>
+{
+    cells
+        .iter()
+        .flat_map(|row| {
+            let wide: Vec<T> = row
+                .iter()
+                .flat_map(|&c| std::iter::repeat_n(c, factor))
+                .collect();
+            std::iter::repeat_n(wide, factor)
+        })
+        .collect()
+}
+
+/// Centres `grid` on the smallest square whose side is a multiple of 64,
+/// as the bitboard engines need.
+pub fn pad_grid_to_multiple_of_64<T: Clone + Default>(
+    grid: Vec<Vec<T>>,
+) -> Vec<Vec<T>>
+{
+    let height = grid.len();
+    let width = grid.first().map(|r| r.len()).unwrap_or(0);
+
+    let target_h = height.div_ceil(64) * 64;
+    let target_w = width.div_ceil(64) * 64;
+
+    let new_size = target_h.max(target_w);
+
+    let pad_vert = new_size - height;
+    let pad_horiz = new_size - width;
+
+    let pad_top = pad_vert / 2;
+    let pad_bottom = pad_vert - pad_top;
+    let pad_left = pad_horiz / 2;
+    let pad_right = pad_horiz - pad_left;
+
+    let mut out = Vec::with_capacity(new_size);
+    let dead_row = vec![T::default(); new_size];
+
+    for _ in 0..pad_top {
+        out.push(dead_row.clone());
+    }
+
+    for mut row in grid {
+        let mut new_row = Vec::with_capacity(new_size);
+        new_row.extend(std::iter::repeat_n(T::default(), pad_left));
+        new_row.append(&mut row);
+        new_row.extend(std::iter::repeat_n(T::default(), pad_right));
+        out.push(new_row);
+    }
+
+    for _ in 0..pad_bottom {
+        out.push(dead_row.clone());
+    }
+
+    out
+}
+
+/// One step of the transformation toolkit, as given on the command line:
+/// `rotate:<degrees>`, `reflect:<x|y>`, `translate:<dx>,<dy>`, `crop`,
+/// `pad:<n>` or `pad:<top>,<right>,<bottom>,<left>`, `tile:<across>x<down>`
+/// and `scale:<factor>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform
+{
+    Rotate(usize),
+    Reflect(Axis),
+    Translate(i64, i64),
+    Crop,
+    Pad
+    {
+        top: usize,
+        right: usize,
+        bottom: usize,
+        left: usize,
+    },
+    Tile(usize, usize),
+    Scale(usize),
+}
+
+impl Transform
+{
+    /// Width and height of the result on a `width`x`height` grid, or None
+    /// if that does not fit in a usize.
+    pub fn result_size(
+        &self,
+        width: usize,
+        height: usize,
+    ) -> Option<(usize, usize)>
+    {
+        match *self {
+            Transform::Rotate(quarter_turns) if quarter_turns % 2 == 1 => {
+                Some((height, width))
+            }
+            Transform::Pad {
+                top,
+                right,
+                bottom,
+                left,
+            } => Some((
+                width.checked_add(left)?.checked_add(right)?,
+                height.checked_add(top)?.checked_add(bottom)?,
+            )),
+            Transform::Tile(across, down) => {
+                Some((width.checked_mul(across)?, height.checked_mul(down)?))
+            }
+            Transform::Scale(factor) => {
+                Some((width.checked_mul(factor)?, height.checked_mul(factor)?))
+            }
+            _ => Some((width, height)),
+        }
+    }
+
+    /// Transforms `cells`, unless the result would be larger than
+    /// MAX_SIDE a side.
+    pub fn apply_to_cells(
+        &self,
+        cells: &[Vec<u8>],
+    ) -> Result<Vec<Vec<u8>>, String>
+    {
+        let (width, height) =
+            (cells.first().map_or(0, |r| r.len()), cells.len());
+        match self.result_size(width, height) {
+            Some((w, h)) if w <= MAX_SIDE && h <= MAX_SIDE => {}
+            _ => {
+                return Err(format!(
+                    "the result would be larger than the {}x{} limit",
+                    MAX_SIDE, MAX_SIDE
+                ))
+            }
+        }
+        Ok(match *self {
+            Transform::Rotate(quarter_turns) => rotate(cells, quarter_turns),
+            Transform::Reflect(axis) => reflect(cells, axis),
+            Transform::Translate(dx, dy) => translate(cells, dx, dy),
+            Transform::Crop => crop(cells),
+            Transform::Pad {
+                top,
+                right,
+                bottom,
+                left,
+            } => pad(cells, top, right, bottom, left),
+            Transform::Tile(across, down) => tile(cells, across, down),
+            Transform::Scale(factor) => scale(cells, factor),
+        })
+    }
+
+    /// Transforms the cells of `pattern`, keeping its rule, name, author
+    /// and comments.
+    pub fn apply(&self, pattern: &Pattern) -> Result<Pattern, String>
+    {
+        let cells = self.apply_to_cells(&pattern.cells)?;
+        Ok(Pattern {
+            width: cells.first().map_or(0, |r| r.len()),
+            height: cells.len(),
+            cells,
+            ..pattern.clone()
+        })
+    }
+}
+
+impl FromStr for Transform
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        let numbers = |expected: usize| -> Result<Vec<i64>, String> {
+            let values = arg
+                .split(',')
+                .map(|v| v.trim().parse::<i64>().map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.len() != expected {
+                return Err(format!("'{}' takes {} numbers", name, expected));
+            }
+            Ok(values)
+        };
+        let count = |v: i64| {
+            usize::try_from(v)
+                .map_err(|_| format!("'{}' needs non-negative numbers", name))
+        };
+
+        match name {
+            "rotate" => match numbers(1)?[0].rem_euclid(360) {
+                d if d % 90 == 0 => Ok(Transform::Rotate(d as usize / 90)),
+                _ => Err("rotation must be a multiple of 90".to_string()),
+            },
+            "reflect" => match arg {
+                "x" => Ok(Transform::Reflect(Axis::X)),
+                "y" => Ok(Transform::Reflect(Axis::Y)),
+                _ => Err("expected 'reflect:x' or 'reflect:y'".to_string()),
+            },
+            "translate" => {
+                let d = numbers(2)?;
+                Ok(Transform::Translate(d[0], d[1]))
+            }
+            "crop" if arg.is_empty() => Ok(Transform::Crop),
+            "pad" => {
+                let m = match arg.contains(',') {
+                    true => numbers(4)?,
+                    false => numbers(1)?.repeat(4),
+                };
+                Ok(Transform::Pad {
+                    top: count(m[0])?,
+                    right: count(m[1])?,
+                    bottom: count(m[2])?,
+                    left: count(m[3])?,
+                })
+            }
+            "tile" => {
+                let (across, down) = arg
+                    .split_once('x')
+                    .ok_or("expected 'tile:<across>x<down>'")?;
+                let parse = |n: &str| {
+                    n.trim().parse::<usize>().map_err(|e| e.to_string())
+                };
+                Ok(Transform::Tile(parse(across)?, parse(down)?))
+            }
+            "scale" => Ok(Transform::Scale(count(numbers(1)?[0])?)),
+            _ => Err(format!("unknown transformation '{}'", s)),
+        }
+    }
+}
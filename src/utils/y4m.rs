@@ -0,0 +1,119 @@
+//! YUV4MPEG2 frame stream for piping runs into a video encoder, e.g.
+//! `... --y4m - | ffmpeg -i - out.mp4`. Frames are 4:4:4 so single-pixel
+//! cells keep their colour.
+
+use std::io::{self, Write};
+
+use crate::utils::{
+    palette::Palette,
+    png::{rasterise, Region},
+};
+
+#[derive(Clone, Debug)]
+pub struct Y4mOptions
+{
+    /// Part of the grid to draw; the whole grid when `None`.
+    pub region: Option<Region>,
+    /// Side of a cell in pixels.
+    pub cell_size: usize,
+    pub fps: u32,
+    pub palette: Palette,
+}
+
+impl Default for Y4mOptions
+{
+    fn default() -> Self
+    {
+        Y4mOptions {
+            region: None,
+            cell_size: 1,
+            fps: 25,
+            palette: Palette::default(),
+        }
+    }
+}
+
+pub struct Y4mWriter<W: Write>
+{
+    out: W,
+    region: Region,
+    cell_size: usize,
+    /// Y, Cb and Cr of every cell state.
+    yuv: Vec<[u8; 3]>,
+}
+
+/// Full-range BT.601 conversion, which is what Y4M readers assume for
+/// 8-bit 4:4:4 input.
+fn to_yuv([r, g, b]: [u8; 3]) -> [u8; 3]
+{
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+    [y, cb, cr].map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+impl<W: Write> Y4mWriter<W>
+{
+    /// Writes the stream header for frames of a `grid_width`x`grid_height`
+    /// grid.
+    pub fn new(
+        mut out: W,
+        grid_width: usize,
+        grid_height: usize,
+        options: &Y4mOptions,
+    ) -> io::Result<Y4mWriter<W>>
+    {
+        let region = options
+            .region
+            .unwrap_or(Region {
+                x: 0,
+                y: 0,
+                width: grid_width,
+                height: grid_height,
+            })
+            .clamp(grid_width, grid_height)?;
+        let cell_size = options.cell_size.max(1);
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444 XCOLORRANGE=FULL",
+            region.width * cell_size,
+            region.height * cell_size,
+            options.fps.max(1)
+        )?;
+        Ok(Y4mWriter {
+            out,
+            region,
+            cell_size,
+            yuv: (0..=255u8)
+                .map(|state| to_yuv(options.palette.colour(state)))
+                .collect(),
+        })
+    }
+
+    /// Appends the flat grid `cells`, laid out `grid_width` cells wide.
+    pub fn add_frame(
+        &mut self,
+        cells: &[u8],
+        grid_width: usize,
+    ) -> io::Result<()>
+    {
+        let pixels = rasterise(cells, grid_width, self.region, self.cell_size);
+        self.out.write_all(b"FRAME\n")?;
+        for plane in 0..3 {
+            let samples: Vec<u8> = pixels
+                .iter()
+                .map(|&state| self.yuv[state as usize][plane])
+                .collect();
+            self.out.write_all(&samples)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the stream and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W>
+    {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}